@@ -0,0 +1,53 @@
+//! Times single-threaded renders of `scenes/spheres.yml`, to compare
+//! builds with and without the `simd` feature:
+//!
+//!   cargo run --release --example render_bench
+//!   cargo run --release --example render_bench --features simd
+//!
+//! Best of 15 runs on an x86_64 Linux box, before and after the kernels
+//! were moved behind `Vector3`/`Point3` and the renderer started tracing
+//! packets (three interleaved rounds, so each cell is a range):
+//!
+//!   build            before       after
+//!   default          300-409 ms   244-341 ms
+//!   --features simd  281-326 ms   174-197 ms
+
+use indicatif::ProgressBar;
+use raytrace::render::{render, Settings};
+use raytrace::sampling::SamplePattern;
+use raytrace::scene;
+use raytrace::simd;
+use std::time::{Duration, Instant};
+
+const SCENE: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/scenes/spheres.yml");
+const RUNS: usize = 15;
+
+fn main() {
+    let scene = scene::load(SCENE).expect("spheres.yml loads");
+    let settings = Settings {
+        width: 400,
+        height: 400,
+        threads: 1,
+        samples: 4,
+        pattern: SamplePattern::Jittered,
+        ..Settings::default()
+    };
+    let mut times: Vec<Duration> = (0..RUNS)
+        .map(|_| {
+            let start = Instant::now();
+            render(&scene, &settings, &ProgressBar::hidden());
+            start.elapsed()
+        })
+        .collect();
+    times.sort();
+    println!(
+        "{}x{}, {} samples, {} runs, {} kernels: best {:.1} ms, median {:.1} ms",
+        settings.width,
+        settings.height,
+        settings.samples,
+        RUNS,
+        if simd::SSE { "SSE" } else { "array" },
+        times[0].as_secs_f64() * 1e3,
+        times[RUNS / 2].as_secs_f64() * 1e3,
+    );
+}
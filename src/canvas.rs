@@ -1,13 +1,47 @@
 use self::super::color::Color;
+use crate::png;
+use crate::Float;
 
+#[derive(Clone)]
 pub struct Canvas {
     pub width: u64,
     pub height: u64,
-    pixels: Vec<Vec<Color>>,
-    // NB: adopting NP [row][col] convention, so [y][x]
+    pixels: Vec<Color>,
+    // NB: row-major, so pixel (x, y) lives at [y * width + x]
+    alpha: Vec<Float>,
+    // coverage per pixel, 1.0 = opaque; colors are premultiplied by it
 }
 
-fn float_to_255(f: f32) -> i32 {
+#[derive(Debug, Clone, PartialEq)]
+pub struct OutOfBounds {
+    pub x: u64,
+    pub y: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ResizeFilter {
+    Nearest,
+    Bilinear,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ImageFormat {
+    Ppm,
+    Png,
+}
+
+impl ImageFormat {
+    /// PNG for a `.png` extension, PPM for anything else.
+    pub fn from_path(path: &str) -> ImageFormat {
+        if path.to_lowercase().ends_with(".png") {
+            ImageFormat::Png
+        } else {
+            ImageFormat::Ppm
+        }
+    }
+}
+
+fn float_to_255(f: Float) -> i32 {
     let f = f * 255.0;
     if f < 0.0 {
         0
@@ -30,7 +64,7 @@ fn color_to_255_str(c: &Color) -> String {
 fn limit_string_to_70(s: String) -> String {
     let mut o = String::new();
     let mut i = 0;
-    let s = s.split(" ");
+    let s = s.split(' ');
     for num in s {
         let num_length = num.len();
         if i + num_length + 1 > 70 {
@@ -39,7 +73,7 @@ fn limit_string_to_70(s: String) -> String {
             i = 0;
         }
         i += num_length + 1;
-        o.push_str(&num);
+        o.push_str(num);
         o.push(' ');
     }
     o.pop();
@@ -51,35 +85,241 @@ impl Canvas {
         Canvas {
             width: w,
             height: h,
-            pixels: vec![vec![Color::default(); w as usize]; h as usize],
+            pixels: vec![Color::default(); (w * h) as usize],
+            alpha: vec![1.; (w * h) as usize],
+        }
+    }
+    fn index(&self, x: u64, y: u64) -> Option<usize> {
+        if x < self.width && y < self.height {
+            Some((y * self.width + x) as usize)
+        } else {
+            None
+        }
+    }
+    pub fn get(&self, x: u64, y: u64) -> Option<Color> {
+        self.index(x, y).map(|i| self.pixels[i].clone())
+    }
+    pub fn get_mut(&mut self, x: u64, y: u64) -> Option<&mut Color> {
+        match self.index(x, y) {
+            Some(i) => Some(&mut self.pixels[i]),
+            None => None,
+        }
+    }
+    pub fn set(&mut self, x: u64, y: u64, c: Color) -> Result<(), OutOfBounds> {
+        match self.get_mut(x, y) {
+            Some(p) => {
+                *p = c;
+                Ok(())
+            }
+            None => Err(OutOfBounds { x, y }),
+        }
+    }
+    pub fn get_alpha(&self, x: u64, y: u64) -> Option<Float> {
+        self.index(x, y).map(|i| self.alpha[i])
+    }
+    pub fn set_alpha(&mut self, x: u64, y: u64, a: Float) -> Result<(), OutOfBounds> {
+        match self.index(x, y) {
+            Some(i) => {
+                self.alpha[i] = a;
+                Ok(())
+            }
+            None => Err(OutOfBounds { x, y }),
         }
     }
-    pub fn get(&self, w: u64, h: u64) -> Color {
-        self.pixels[h as usize][w as usize].clone()
+    /// Rows from top (y = 0) to bottom, each `width` pixels long.
+    pub fn rows(&self) -> impl Iterator<Item = &[Color]> {
+        self.pixels.chunks(self.width.max(1) as usize)
     }
-    pub fn set(&mut self, w: u64, h: u64, c: Color) {
-        let row = &mut self.pixels[h as usize];
-        let w = w as usize;
-        row.remove(w);
-        row.insert(w, c);
+    pub fn rows_mut(&mut self) -> impl Iterator<Item = &mut [Color]> {
+        self.pixels.chunks_mut(self.width.max(1) as usize)
+    }
+    /// Every pixel in row-major order, as `(x, y, color)`.
+    pub fn pixels(&self) -> impl Iterator<Item = (u64, u64, &Color)> {
+        let w = self.width;
+        self.pixels
+            .iter()
+            .enumerate()
+            .map(move |(i, c)| (i as u64 % w, i as u64 / w, c))
+    }
+    pub fn pixels_mut(&mut self) -> impl Iterator<Item = (u64, u64, &mut Color)> {
+        let w = self.width;
+        self.pixels
+            .iter_mut()
+            .enumerate()
+            .map(move |(i, c)| (i as u64 % w, i as u64 / w, c))
+    }
+    /// Copies out the `w` x `h` rectangle whose top-left corner is `(x, y)`.
+    pub fn crop(&self, x: u64, y: u64, w: u64, h: u64) -> Result<Canvas, OutOfBounds> {
+        if x + w > self.width || y + h > self.height {
+            return Err(OutOfBounds { x: x + w, y: y + h });
+        }
+        let mut out = Canvas::new(w, h);
+        for i in 0..(w * h) as usize {
+            let (dx, dy) = (i as u64 % w, i as u64 / w);
+            let src = ((y + dy) * self.width + x + dx) as usize;
+            out.pixels[i] = self.pixels[src].clone();
+            out.alpha[i] = self.alpha[src];
+        }
+        Ok(out)
+    }
+    fn sample_bilinear(&self, u: Float, v: Float) -> (Color, Float) {
+        // u, v are in source pixel units, with pixel centers at integer + 0.5
+        let max_x = (self.width - 1) as Float;
+        let max_y = (self.height - 1) as Float;
+        let fx = (u - 0.5).max(0.).min(max_x);
+        let fy = (v - 0.5).max(0.).min(max_y);
+        let (x0, y0) = (fx.floor() as u64, fy.floor() as u64);
+        let (x1, y1) = ((x0 + 1).min(self.width - 1), (y0 + 1).min(self.height - 1));
+        let (tx, ty) = (fx - x0 as Float, fy - y0 as Float);
+        let taps = [
+            (x0, y0, (1. - tx) * (1. - ty)),
+            (x1, y0, tx * (1. - ty)),
+            (x0, y1, (1. - tx) * ty),
+            (x1, y1, tx * ty),
+        ];
+        let mut color = Color::default();
+        let mut alpha = 0.;
+        for &(x, y, weight) in taps.iter() {
+            let i = (y * self.width + x) as usize;
+            color = color.add(self.pixels[i].multiply(weight));
+            alpha += self.alpha[i] * weight;
+        }
+        (color, alpha)
+    }
+    pub fn resize(&self, w: u64, h: u64, filter: ResizeFilter) -> Canvas {
+        let mut out = Canvas::new(w, h);
+        if self.width == 0 || self.height == 0 {
+            return out;
+        }
+        let sx = self.width as Float / w as Float;
+        let sy = self.height as Float / h as Float;
+        for i in 0..(w * h) as usize {
+            let u = ((i as u64 % w) as Float + 0.5) * sx;
+            let v = ((i as u64 / w) as Float + 0.5) * sy;
+            let (color, alpha) = match filter {
+                ResizeFilter::Nearest => {
+                    let src_x = (u as u64).min(self.width - 1);
+                    let src_y = (v as u64).min(self.height - 1);
+                    let src = (src_y * self.width + src_x) as usize;
+                    (self.pixels[src].clone(), self.alpha[src])
+                }
+                ResizeFilter::Bilinear => self.sample_bilinear(u, v),
+            };
+            out.pixels[i] = color;
+            out.alpha[i] = alpha;
+        }
+        out
+    }
+    /// Draws `src` with its top-left corner at `(x, y)`, clipping whatever
+    /// falls outside this canvas.
+    pub fn blit(&mut self, src: &Canvas, x: u64, y: u64) {
+        for (i, (sx, sy, c)) in src.pixels().enumerate() {
+            if let Some(d) = self.index(x + sx, y + sy) {
+                self.pixels[d] = c.clone();
+                self.alpha[d] = src.alpha[i];
+            }
+        }
+    }
+    /// Like `blit`, but blends `src` over what is already here using its
+    /// coverage (the "over" operator on premultiplied colors).
+    pub fn composite(&mut self, src: &Canvas, x: u64, y: u64) {
+        for (i, (sx, sy, c)) in src.pixels().enumerate() {
+            if let Some(d) = self.index(x + sx, y + sy) {
+                let a = src.alpha[i];
+                self.pixels[d] = c.add(self.pixels[d].multiply(1. - a));
+                self.alpha[d] = a + self.alpha[d] * (1. - a);
+            }
+        }
+    }
+    /// Mirrors the image top-to-bottom.
+    pub fn flip_vertical(&mut self) {
+        let w = self.width as usize;
+        let h = self.height as usize;
+        for y in 0..h / 2 {
+            let (top, bottom) = self.pixels.split_at_mut((h - 1 - y) * w);
+            top[y * w..(y + 1) * w].swap_with_slice(&mut bottom[..w]);
+            let (top, bottom) = self.alpha.split_at_mut((h - 1 - y) * w);
+            top[y * w..(y + 1) * w].swap_with_slice(&mut bottom[..w]);
+        }
+    }
+    /// Mirrors the image left-to-right.
+    pub fn flip_horizontal(&mut self) {
+        for row in self.rows_mut() {
+            row.reverse();
+        }
+        for row in self.alpha.chunks_mut(self.width.max(1) as usize) {
+            row.reverse();
+        }
     }
-    fn get_ppm_from_row(&self, row: &Vec<Color>) -> String {
+    fn get_ppm_from_row(&self, row: &[Color]) -> String {
         let col_strs: Vec<String> = row.iter().map(color_to_255_str).collect();
         limit_string_to_70(col_strs.join(" "))
     }
+    /// Parses a plain (P3) PPM, scaling samples by the declared max value.
+    pub fn from_ppm(s: &str) -> Result<Canvas, String> {
+        let mut tokens = s
+            .lines()
+            .map(|l| l.split('#').next().unwrap_or(""))
+            .flat_map(|l| l.split_whitespace());
+        if tokens.next() != Some("P3") {
+            return Err(String::from("not a P3 ppm"));
+        }
+        let mut next_num = |what: &str| -> Result<u64, String> {
+            let t = tokens.next().ok_or_else(|| format!("missing {}", what))?;
+            t.parse::<u64>()
+                .map_err(|_| format!("bad {}: {:?}", what, t))
+        };
+        let width = next_num("width")?;
+        let height = next_num("height")?;
+        let max = next_num("max value")?.max(1) as Float;
+        let mut canvas = Canvas::new(width, height);
+        for (_, _, p) in canvas.pixels_mut() {
+            p.r = next_num("sample")? as Float / max;
+            p.g = next_num("sample")? as Float / max;
+            p.b = next_num("sample")? as Float / max;
+        }
+        Ok(canvas)
+    }
+    /// Encodes as an 8-bit RGBA PNG, un-premultiplying colors by coverage.
+    pub fn to_png(&self) -> Vec<u8> {
+        let mut rgba = Vec::with_capacity(self.pixels.len() * 4);
+        for (c, &a) in self.pixels.iter().zip(self.alpha.iter()) {
+            let straight = if a > 0. {
+                c.multiply(1. / a)
+            } else {
+                c.clone()
+            };
+            rgba.push(float_to_255(straight.r) as u8);
+            rgba.push(float_to_255(straight.g) as u8);
+            rgba.push(float_to_255(straight.b) as u8);
+            rgba.push(float_to_255(a) as u8);
+        }
+        png::encode_rgba(self.width as u32, self.height as u32, &rgba)
+    }
+    /// Writes in the format implied by the extension of `path`.
+    pub fn save(&self, path: &str) -> std::io::Result<()> {
+        self.save_as(path, ImageFormat::from_path(path))
+    }
+    /// The image goes to a temporary file that is then renamed over `path`,
+    /// so a reader never sees it half-written.
+    pub fn save_as(&self, path: &str, format: ImageFormat) -> std::io::Result<()> {
+        let bytes = match format {
+            ImageFormat::Png => self.to_png(),
+            ImageFormat::Ppm => self.to_ppm().into_bytes(),
+        };
+        let tmp = format!("{}.tmp", path);
+        std::fs::write(&tmp, bytes)?;
+        std::fs::rename(&tmp, path)
+    }
     pub fn to_ppm(&self) -> String {
-        let header = String::from(format!(
+        let header = format!(
             "P3\n\
         {} {}\n\
         255\n\
         ",
             self.width, self.height
-        ));
-        let mapped_cols: Vec<String> = self
-            .pixels
-            .iter()
-            .map(|x| self.get_ppm_from_row(x))
-            .collect();
+        );
+        let mapped_cols: Vec<String> = self.rows().map(|x| self.get_ppm_from_row(x)).collect();
         let content = mapped_cols.join("\n");
         format!("{}{}\n", header, content)
     }
@@ -92,7 +332,7 @@ mod canvas_tests {
     #[test]
     fn empty_canvas() {
         let c = Canvas::new(20, 40);
-        assert!(c.get(10, 30) == Color::default())
+        assert!(c.get(10, 30) == Some(Color::default()))
     }
 
     #[test]
@@ -106,16 +346,288 @@ mod canvas_tests {
                 g: 0.3,
                 b: 0.4,
             },
-        );
-        assert!(c.get(10, 30) == Color::default());
+        )
+        .expect("in bounds");
+        assert!(c.get(10, 30) == Some(Color::default()));
         assert!(
             c.get(12, 22)
-                == Color {
+                == Some(Color {
                     r: 0.2,
                     g: 0.3,
                     b: 0.4
+                })
+        )
+    }
+
+    #[test]
+    fn out_of_bounds() {
+        let mut c = Canvas::new(20, 40);
+        assert!(c.get(20, 0).is_none());
+        assert!(c.get(0, 40).is_none());
+        assert_eq!(
+            c.set(25, 3, Color::default()),
+            Err(OutOfBounds { x: 25, y: 3 })
+        );
+    }
+
+    #[test]
+    fn iterators() {
+        let mut c = Canvas::new(3, 2);
+        for (x, y, p) in c.pixels_mut() {
+            p.r = x as Float;
+            p.g = y as Float;
+        }
+        let rows: Vec<&[Color]> = c.rows().collect();
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[1].len(), 3);
+        assert!(
+            rows[1][2]
+                == Color {
+                    r: 2.,
+                    g: 1.,
+                    b: 0.
                 }
+        );
+        assert_eq!(c.pixels().count(), 6);
+        assert!(
+            c.get(1, 1)
+                == Some(Color {
+                    r: 1.,
+                    g: 1.,
+                    b: 0.
+                })
+        );
+    }
+
+    fn gradient(w: u64, h: u64) -> Canvas {
+        let mut c = Canvas::new(w, h);
+        for (x, y, p) in c.pixels_mut() {
+            p.r = x as Float;
+            p.g = y as Float;
+        }
+        c
+    }
+
+    #[test]
+    fn crop() {
+        let c = gradient(4, 3);
+        let cropped = c.crop(1, 1, 2, 2).expect("in bounds");
+        assert_eq!((cropped.width, cropped.height), (2, 2));
+        assert!(
+            cropped.get(0, 0)
+                == Some(Color {
+                    r: 1.,
+                    g: 1.,
+                    b: 0.
+                })
+        );
+        assert!(
+            cropped.get(1, 1)
+                == Some(Color {
+                    r: 2.,
+                    g: 2.,
+                    b: 0.
+                })
+        );
+        assert!(c.crop(3, 0, 2, 1).is_err());
+    }
+
+    #[test]
+    fn resize_nearest() {
+        let c = gradient(2, 2);
+        let big = c.resize(4, 4, ResizeFilter::Nearest);
+        assert!(
+            big.get(0, 0)
+                == Some(Color {
+                    r: 0.,
+                    g: 0.,
+                    b: 0.
+                })
+        );
+        assert!(
+            big.get(1, 1)
+                == Some(Color {
+                    r: 0.,
+                    g: 0.,
+                    b: 0.
+                })
+        );
+        assert!(
+            big.get(2, 3)
+                == Some(Color {
+                    r: 1.,
+                    g: 1.,
+                    b: 0.
+                })
+        );
+        let small = big.resize(2, 2, ResizeFilter::Nearest);
+        assert!(small.get(1, 0) == c.get(1, 0));
+    }
+
+    #[test]
+    fn resize_bilinear() {
+        let c = gradient(2, 1);
+        let big = c.resize(4, 1, ResizeFilter::Bilinear);
+        assert!(
+            big.get(0, 0)
+                == Some(Color {
+                    r: 0.,
+                    g: 0.,
+                    b: 0.
+                })
+        );
+        assert!(
+            big.get(1, 0)
+                == Some(Color {
+                    r: 0.25,
+                    g: 0.,
+                    b: 0.
+                })
+        );
+        assert!(
+            big.get(2, 0)
+                == Some(Color {
+                    r: 0.75,
+                    g: 0.,
+                    b: 0.
+                })
+        );
+        assert!(
+            big.get(3, 0)
+                == Some(Color {
+                    r: 1.,
+                    g: 0.,
+                    b: 0.
+                })
+        );
+    }
+
+    #[test]
+    fn blit() {
+        let mut dst = Canvas::new(3, 3);
+        let src = gradient(2, 2);
+        dst.blit(&src, 2, 1);
+        assert!(
+            dst.get(2, 1)
+                == Some(Color {
+                    r: 0.,
+                    g: 0.,
+                    b: 0.
+                })
+        );
+        assert!(
+            dst.get(2, 2)
+                == Some(Color {
+                    r: 0.,
+                    g: 1.,
+                    b: 0.
+                })
+        );
+        assert!(dst.get(1, 1) == Some(Color::default()));
+    }
+
+    #[test]
+    fn flip() {
+        let mut c = gradient(3, 3);
+        c.flip_vertical();
+        assert!(
+            c.get(1, 0)
+                == Some(Color {
+                    r: 1.,
+                    g: 2.,
+                    b: 0.
+                })
+        );
+        assert!(
+            c.get(1, 1)
+                == Some(Color {
+                    r: 1.,
+                    g: 1.,
+                    b: 0.
+                })
+        );
+        c.flip_horizontal();
+        assert!(
+            c.get(0, 2)
+                == Some(Color {
+                    r: 2.,
+                    g: 0.,
+                    b: 0.
+                })
+        );
+    }
+
+    #[test]
+    fn alpha() {
+        let mut c = gradient(3, 3);
+        assert_eq!(c.get_alpha(1, 1), Some(1.));
+        c.set_alpha(0, 0, 0.25).expect("in bounds");
+        assert!(c.set_alpha(3, 0, 0.).is_err());
+        c.flip_vertical();
+        c.flip_horizontal();
+        assert_eq!(c.get_alpha(2, 2), Some(0.25));
+        let cropped = c.crop(1, 1, 2, 2).expect("in bounds");
+        assert_eq!(cropped.get_alpha(1, 1), Some(0.25));
+        let half = c.resize(6, 6, ResizeFilter::Nearest);
+        assert_eq!(half.get_alpha(5, 5), Some(0.25));
+    }
+
+    #[test]
+    fn composite() {
+        let mut bg = Canvas::new(2, 1);
+        for (_, _, p) in bg.pixels_mut() {
+            p.b = 1.;
+        }
+        let mut fg = Canvas::new(2, 1);
+        fg.set(
+            0,
+            0,
+            Color {
+                r: 0.5,
+                g: 0.,
+                b: 0.,
+            },
+        )
+        .expect("in bounds");
+        fg.set_alpha(0, 0, 0.5).expect("in bounds");
+        fg.set_alpha(1, 0, 0.).expect("in bounds");
+        bg.composite(&fg, 0, 0);
+        assert!(
+            bg.get(0, 0)
+                == Some(Color {
+                    r: 0.5,
+                    g: 0.,
+                    b: 0.5
+                })
+        );
+        assert!(
+            bg.get(1, 0)
+                == Some(Color {
+                    r: 0.,
+                    g: 0.,
+                    b: 1.
+                })
+        );
+        assert_eq!(bg.get_alpha(0, 0), Some(1.));
+    }
+
+    #[test]
+    fn png_alpha() {
+        let mut c = Canvas::new(1, 1);
+        c.set(
+            0,
+            0,
+            Color {
+                r: 0.5,
+                g: 0.,
+                b: 0.,
+            },
         )
+        .expect("in bounds");
+        c.set_alpha(0, 0, 0.5).expect("in bounds");
+        let png = c.to_png();
+        // one filter byte, then straight (un-premultiplied) RGBA
+        assert_eq!(png[48..53], [0, 255, 0, 0, 128]);
     }
 
     #[test]
@@ -129,7 +641,8 @@ mod canvas_tests {
                 g: 0.0,
                 b: 0.0,
             },
-        );
+        )
+        .expect("in bounds");
         c.set(
             2,
             1,
@@ -138,7 +651,8 @@ mod canvas_tests {
                 g: 0.5,
                 b: 0.0,
             },
-        );
+        )
+        .expect("in bounds");
         c.set(
             4,
             2,
@@ -147,7 +661,8 @@ mod canvas_tests {
                 g: 0.0,
                 b: 1.0,
             },
-        );
+        )
+        .expect("in bounds");
         assert_eq!(
             c.to_ppm(),
             String::from(
@@ -161,6 +676,49 @@ mod canvas_tests {
         );
     }
 
+    #[test]
+    fn ppm_round_trip() {
+        let mut c = Canvas::new(3, 2);
+        c.set(
+            1,
+            1,
+            Color {
+                r: 1.,
+                g: 0.2,
+                b: 0.,
+            },
+        )
+        .expect("in bounds");
+        let parsed = Canvas::from_ppm(&c.to_ppm()).expect("valid ppm");
+        assert_eq!((parsed.width, parsed.height), (3, 2));
+        assert!(
+            parsed.get(1, 1)
+                == Some(Color {
+                    r: 1.,
+                    g: 51. / 255.,
+                    b: 0.
+                })
+        );
+        assert!(parsed.get(0, 0) == Some(Color::default()));
+        assert!(Canvas::from_ppm("P3\n# comment\n1 1\n255\n0 0").is_err());
+        assert!(Canvas::from_ppm("P6\n1 1\n255\n").is_err());
+    }
+
+    #[test]
+    fn save() {
+        let dir = std::env::temp_dir();
+        let c = gradient(3, 2);
+        let ppm = dir.join(format!("canvas_save_{}.ppm", std::process::id()));
+        let png = dir.join(format!("canvas_save_{}.PNG", std::process::id()));
+        let (ppm, png) = (ppm.to_str().expect("utf8"), png.to_str().expect("utf8"));
+        c.save(ppm).expect("writable");
+        c.save(png).expect("writable");
+        assert_eq!(std::fs::read_to_string(ppm).expect("written"), c.to_ppm());
+        assert_eq!(std::fs::read(png).expect("written"), c.to_png());
+        std::fs::remove_file(ppm).expect("exists");
+        std::fs::remove_file(png).expect("exists");
+    }
+
     #[test]
     fn line_limit() {
         assert_eq!(
@@ -0,0 +1,405 @@
+use crate::canvas::Canvas;
+use crate::render::{render_tiles, Accumulator, Frame, Scene, Settings};
+use crate::scene;
+use crate::tiles::tiles;
+use crate::Float;
+use indicatif::ProgressBar;
+use std::time::{Duration, Instant};
+
+const MAGIC: &[u8] = b"RTCHECKPOINT1\n";
+
+/// Everything needed to pick a render back up: the finished passes, the
+/// tiles done so far in the pass in flight, and a fingerprint of the
+/// scene and settings that produced them. Per-pixel random streams are derived from
+/// `settings.seed` and the pass number, so those two are the whole RNG state.
+pub struct Checkpoint {
+    pub fingerprint: String,
+    /// Passes the render is meant to run in total.
+    pub passes: u32,
+    pub acc: Accumulator,
+    pub pass: Frame,
+    pub done_tiles: Vec<usize>,
+}
+
+/// Settings that change the image or the tile numbering; a checkpoint only
+/// resumes under the same ones, for the same scene, and with the same
+/// `Float` precision. Thread count is deliberately left out.
+fn fingerprint(scene: &Scene, settings: &Settings, passes: u32) -> String {
+    format!(
+        "{}x{} seed={} samples={} pattern={:?} filter={:?} adaptive={:?} depth={} tiles={}/{:?} passes={} float={} scene={:016x}",
+        settings.width,
+        settings.height,
+        settings.seed,
+        settings.samples,
+        settings.pattern,
+        settings.filter,
+        settings.adaptive,
+        settings.max_depth,
+        settings.tile_size,
+        settings.tile_order,
+        passes,
+        8 * std::mem::size_of::<Float>(),
+        digest(scene::to_json(scene).as_bytes())
+    )
+}
+
+/// 64-bit FNV-1a, which unlike `DefaultHasher` is the same on every build.
+fn digest(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |h, &b| {
+        (h ^ b as u64).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+fn write_canvas(out: &mut Vec<u8>, c: &Canvas) {
+    out.extend_from_slice(&c.width.to_le_bytes());
+    out.extend_from_slice(&c.height.to_le_bytes());
+    for (x, y, p) in c.pixels() {
+        let a = c.get_alpha(x, y).expect("pixel in bounds");
+        for v in [p.r, p.g, p.b, a].iter() {
+            out.extend_from_slice(&v.to_le_bytes());
+        }
+    }
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, n: usize) -> Result<&'a [u8], String> {
+        if self.bytes.len() < n {
+            return Err(String::from("checkpoint is truncated"));
+        }
+        let (head, rest) = self.bytes.split_at(n);
+        self.bytes = rest;
+        Ok(head)
+    }
+    fn u32(&mut self) -> Result<u32, String> {
+        let mut b = [0; 4];
+        b.copy_from_slice(self.take(4)?);
+        Ok(u32::from_le_bytes(b))
+    }
+    fn u64(&mut self) -> Result<u64, String> {
+        let mut b = [0; 8];
+        b.copy_from_slice(self.take(8)?);
+        Ok(u64::from_le_bytes(b))
+    }
+    fn float(&mut self) -> Result<Float, String> {
+        const N: usize = std::mem::size_of::<Float>();
+        let mut b = [0; N];
+        b.copy_from_slice(self.take(N)?);
+        Ok(Float::from_le_bytes(b))
+    }
+    fn canvas(&mut self, width: u64, height: u64) -> Result<Canvas, String> {
+        if (self.u64()?, self.u64()?) != (width, height) {
+            return Err(String::from("checkpoint canvas has the wrong size"));
+        }
+        let mut c = Canvas::new(width, height);
+        for y in 0..height {
+            for x in 0..width {
+                let p = c.get_mut(x, y).expect("pixel in bounds");
+                p.r = self.float()?;
+                p.g = self.float()?;
+                p.b = self.float()?;
+                let a = self.float()?;
+                c.set_alpha(x, y, a).expect("pixel in bounds");
+            }
+        }
+        Ok(c)
+    }
+    fn frame(&mut self, into: &mut Frame) -> Result<(), String> {
+        for c in into.passes_mut().iter_mut() {
+            **c = self.canvas(c.width, c.height)?;
+        }
+        Ok(())
+    }
+}
+
+impl Checkpoint {
+    pub fn new(scene: &Scene, settings: &Settings, passes: u32) -> Checkpoint {
+        let passes = passes.max(1);
+        Checkpoint {
+            fingerprint: fingerprint(scene, settings, passes),
+            passes,
+            acc: Accumulator::new(settings.width, settings.height),
+            pass: Frame::new(settings.width, settings.height),
+            done_tiles: vec![],
+        }
+    }
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = MAGIC.to_vec();
+        out.extend_from_slice(&(self.fingerprint.len() as u32).to_le_bytes());
+        out.extend_from_slice(self.fingerprint.as_bytes());
+        out.extend_from_slice(&self.acc.passes.to_le_bytes());
+        out.extend_from_slice(&(self.done_tiles.len() as u32).to_le_bytes());
+        for &t in self.done_tiles.iter() {
+            out.extend_from_slice(&(t as u64).to_le_bytes());
+        }
+        write_canvas(&mut out, &self.acc.sum);
+        for c in self
+            .acc
+            .frame
+            .passes()
+            .iter()
+            .chain(self.pass.passes().iter())
+        {
+            write_canvas(&mut out, c);
+        }
+        out
+    }
+    /// Parses a checkpoint written for the same `scene`, `settings` and
+    /// `passes`.
+    pub fn from_bytes(
+        bytes: &[u8],
+        scene: &Scene,
+        settings: &Settings,
+        passes: u32,
+    ) -> Result<Checkpoint, String> {
+        let mut r = Reader { bytes };
+        if r.take(MAGIC.len())? != MAGIC {
+            return Err(String::from("not a checkpoint file"));
+        }
+        let len = r.u32()? as usize;
+        let found = String::from_utf8_lossy(r.take(len)?).into_owned();
+        let mut cp = Checkpoint::new(scene, settings, passes);
+        if found != cp.fingerprint {
+            return Err(format!(
+                "checkpoint was made with a different scene or settings ({} vs {})",
+                found, cp.fingerprint
+            ));
+        }
+        cp.acc.passes = r.u32()?;
+        for _ in 0..r.u32()? {
+            cp.done_tiles.push(r.u64()? as usize);
+        }
+        cp.acc.sum = r.canvas(settings.width, settings.height)?;
+        r.frame(&mut cp.acc.frame)?;
+        r.frame(&mut cp.pass)?;
+        if !r.bytes.is_empty() {
+            return Err(String::from("trailing bytes after checkpoint"));
+        }
+        Ok(cp)
+    }
+    /// Writes to a temporary file and renames it over `path`, so a crash
+    /// mid-write leaves the previous checkpoint intact.
+    pub fn save(&self, path: &str) -> std::io::Result<()> {
+        let tmp = format!("{}.tmp", path);
+        std::fs::write(&tmp, self.to_bytes())?;
+        std::fs::rename(&tmp, path)
+    }
+    pub fn load(
+        path: &str,
+        scene: &Scene,
+        settings: &Settings,
+        passes: u32,
+    ) -> Result<Checkpoint, String> {
+        let bytes = std::fs::read(path).map_err(|e| format!("{}: {}", path, e))?;
+        Checkpoint::from_bytes(&bytes, scene, settings, passes)
+            .map_err(|e| format!("{}: {}", path, e))
+    }
+}
+
+/// Runs the passes left in `cp` like `render_progressive` (a single pass
+/// is a plain `render`), saving a checkpoint to `path` at most every
+/// `every` and after each pass. Start from `Checkpoint::new` or a loaded
+/// one; the finished image is the same however many times the render was
+/// interrupted. The checkpoint file is removed once the render is done.
+/// `on_pass` sees the averaged frame after every pass.
+pub fn render_checkpointed<F>(
+    scene: &Scene,
+    settings: &Settings,
+    mut cp: Checkpoint,
+    path: &str,
+    every: Duration,
+    bar: &ProgressBar,
+    mut on_pass: F,
+) -> std::io::Result<Frame>
+where
+    F: FnMut(u32, &Frame),
+{
+    let passes = cp.passes;
+    let all_tiles = tiles(
+        settings.width,
+        settings.height,
+        settings.tile_size,
+        settings.tile_order,
+    );
+    bar.set_length(all_tiles.len() as u64 * passes as u64);
+    bar.set_position((all_tiles.len() * cp.acc.passes as usize + cp.done_tiles.len()) as u64);
+    let mut last_save = Instant::now();
+    while cp.acc.passes < passes {
+        let pass_settings = if passes == 1 {
+            settings.clone()
+        } else {
+            Accumulator::pass_settings(settings, cp.acc.passes)
+        };
+        let todo: Vec<_> = all_tiles
+            .iter()
+            .filter(|t| !cp.done_tiles.contains(&t.index))
+            .cloned()
+            .collect();
+        let mut result = Ok(());
+        render_tiles(scene, &pass_settings, &todo, |tile, pixels| {
+            cp.pass.blit(&pixels, tile.x, tile.y);
+            cp.done_tiles.push(tile.index);
+            bar.inc(1);
+            if result.is_ok() && last_save.elapsed() >= every {
+                result = cp.save(path);
+                last_save = Instant::now();
+            }
+        });
+        result?;
+        let pass = std::mem::replace(&mut cp.pass, Frame::new(settings.width, settings.height));
+        cp.acc.add(&pass);
+        cp.done_tiles.clear();
+        cp.save(path)?;
+        last_save = Instant::now();
+        on_pass(cp.acc.passes, &cp.acc.frame());
+    }
+    std::fs::remove_file(path)?;
+    Ok(cp.acc.frame())
+}
+
+#[cfg(test)]
+mod checkpoint_tests {
+    use super::*;
+    use crate::color::Color;
+    use crate::compare::compare;
+    use crate::light::point_light;
+    use crate::matrix::matrix::translation;
+    use crate::render::Camera;
+    use crate::sampling::{Filter, SamplePattern};
+    use crate::shapes;
+    use crate::tuple::Point3;
+
+    fn scene() -> Scene {
+        Scene {
+            camera: Camera::default(),
+            lights: vec![],
+            shapes: vec![
+                Box::new(shapes::sphere_unit()),
+                Box::new(shapes::sphere(translation(1., 1., 1.))),
+            ],
+        }
+    }
+
+    fn settings() -> Settings {
+        Settings {
+            width: 12,
+            height: 10,
+            threads: 2,
+            tile_size: 4,
+            samples: 2,
+            pattern: SamplePattern::Jittered,
+            filter: Filter::Tent,
+            seed: 9,
+            ..Settings::default()
+        }
+    }
+
+    fn temp_path(name: &str) -> String {
+        let p = std::env::temp_dir().join(format!("{}_{}.ckpt", name, std::process::id()));
+        p.to_str().expect("utf8").to_string()
+    }
+
+    fn same(a: &Frame, b: &Frame) {
+        for (ca, cb) in a.passes().iter().zip(b.passes().iter()) {
+            assert_eq!(compare(ca, cb, 0.).expect("same size").max_error, 0.);
+            for (x, y, _) in ca.pixels() {
+                assert_eq!(ca.get_alpha(x, y), cb.get_alpha(x, y));
+            }
+        }
+    }
+
+    #[test]
+    fn round_trip() {
+        let (sc, s) = (scene(), settings());
+        let mut cp = Checkpoint::new(&sc, &s, 3);
+        cp.acc.passes = 2;
+        cp.done_tiles = vec![4, 1];
+        cp.pass.depth.set_alpha(3, 2, 0.5).expect("in bounds");
+        cp.acc.sum.get_mut(1, 1).expect("in bounds").g = 7.25;
+        let bytes = cp.to_bytes();
+        let back = Checkpoint::from_bytes(&bytes, &sc, &s, 3).expect("valid");
+        assert_eq!(back.acc.passes, 2);
+        assert_eq!(back.done_tiles, vec![4, 1]);
+        assert_eq!(back.pass.depth.get_alpha(3, 2), Some(0.5));
+        assert_eq!(back.acc.sum.get(1, 1).expect("in bounds").g, 7.25);
+
+        assert!(Checkpoint::from_bytes(&bytes, &sc, &s, 4).is_err());
+        let other = Settings {
+            seed: 10,
+            ..settings()
+        };
+        assert!(Checkpoint::from_bytes(&bytes, &sc, &other, 3).is_err());
+        assert!(Checkpoint::from_bytes(&bytes[..bytes.len() - 1], &sc, &s, 3).is_err());
+        assert!(Checkpoint::from_bytes(b"P3\n", &sc, &s, 3).is_err());
+    }
+
+    #[test]
+    fn changed_scene() {
+        let (sc, s) = (scene(), settings());
+        let path = temp_path("changed_scene");
+        Checkpoint::new(&sc, &s, 3).save(&path).expect("writable");
+        assert!(Checkpoint::load(&path, &scene(), &s, 3).is_ok());
+
+        let mut moved = scene();
+        moved.shapes[1] = Box::new(shapes::sphere(translation(1., 1., 1.5)));
+        let mut camera = scene();
+        camera.camera.eye = Point3::new(0., 0., -6.);
+        let mut lit = scene();
+        lit.lights
+            .push(point_light(Point3::new(-10., 10., -10.), Color::default()));
+        for other in [moved, camera, lit].iter() {
+            let e = Checkpoint::load(&path, other, &s, 3)
+                .err()
+                .expect("rejected");
+            assert!(e.contains("different scene or settings"));
+        }
+        std::fs::remove_file(&path).expect("removable");
+    }
+
+    #[test]
+    fn resume_matches_uninterrupted() {
+        let (sc, s) = (scene(), settings());
+        let bar = ProgressBar::hidden();
+        for &passes in [1, 3].iter() {
+            let path = temp_path(&format!("resume_{}", passes));
+            let fresh = Checkpoint::new(&sc, &s, passes);
+            let hour = Duration::from_secs(3600);
+            let full = render_checkpointed(&sc, &s, fresh, &path, hour, &bar, |_, _| {})
+                .expect("writable");
+            assert!(std::fs::metadata(&path).is_err());
+
+            // fake a crash partway through the last pass: some tiles done
+            let mut cp = Checkpoint::new(&sc, &s, passes);
+            let ts = tiles(s.width, s.height, s.tile_size, s.tile_order);
+            for p in 0..passes - 1 {
+                let ps = Accumulator::pass_settings(&s, p);
+                let mut frame = Frame::new(s.width, s.height);
+                render_tiles(&sc, &ps, &ts, |t, px| frame.blit(&px, t.x, t.y));
+                cp.acc.add(&frame);
+            }
+            let ps = if passes == 1 {
+                s.clone()
+            } else {
+                Accumulator::pass_settings(&s, passes - 1)
+            };
+            render_tiles(&sc, &ps, &ts[..3], |t, px| {
+                cp.pass.blit(&px, t.x, t.y);
+                cp.done_tiles.push(t.index);
+            });
+            cp.save(&path).expect("writable");
+
+            let loaded = Checkpoint::load(&path, &sc, &s, passes).expect("valid");
+            let mut seen = vec![];
+            let always = Duration::from_secs(0);
+            let resumed =
+                render_checkpointed(&sc, &s, loaded, &path, always, &bar, |p, _| seen.push(p))
+                    .expect("writable");
+            assert_eq!(seen, vec![passes]);
+            same(&full, &resumed);
+            assert!(std::fs::metadata(&path).is_err());
+        }
+    }
+}
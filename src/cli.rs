@@ -0,0 +1,382 @@
+use crate::canvas::ImageFormat;
+use crate::render::{Adaptive, Settings};
+use crate::sampling::{Filter, SamplePattern};
+use crate::tiles::TileOrder;
+use std::time::Duration;
+
+pub const USAGE: &str = "\
+Usage: raytrace [options]
+
+Output:
+  -o, --output <path>         image to write [default: output.ppm]
+      --format <ppm|png>      image format [default: from the extension]
+  -q, --quiet                 no progress bar
+
+Image:
+      --width <px>            [default: 200]
+      --height <px>           [default: 200]
+      --scene <path>          YAML scene file to render instead of the demo scene
+      --dump-scene <path>     write the scene as YAML, or JSON for a .json path,
+                              and exit without rendering
+
+Sampling:
+  -s, --samples <n>           samples per pixel [default: 1]
+      --pattern <name>        regular, jittered, halton or sobol [default: regular]
+      --filter <name>         box, tent, gaussian or mitchell [default: box]
+      --seed <n>              [default: 0]
+      --max-samples <n>       sample adaptively, up to n per pixel
+      --threshold <f>         adaptive noise target, with --max-samples [default: 0.01]
+      --max-depth <n>         bounce limit for secondary rays; no effect until
+                              reflections are traced [default: 5]
+
+Execution:
+  -j, --threads <n>           worker threads, 0 for one per core [default: 0]
+      --tile-size <px>        [default: 16]
+      --tile-order <name>     scanline, spiral or hilbert [default: scanline]
+      --passes <n>            progressive passes, one jittered sample each; not
+                              with the sampling options above [default: 1]
+      --snapshot-every <n>    write the image every n passes [default: 1]
+      --checkpoint <path>     [default: <output>.ckpt]
+      --checkpoint-every <s>  save a checkpoint every s seconds; none are saved
+                              without this or --resume [default: 30]
+      --resume                continue from the checkpoint
+
+  -h, --help                  print this help
+";
+
+/// Sampling options that progressive passes replace with their own.
+const PER_PIXEL: &[&str] = &[
+    "-s",
+    "--samples",
+    "--pattern",
+    "--max-samples",
+    "--threshold",
+];
+
+const TAKES_VALUE: &[&str] = &[
+    "-o",
+    "--output",
+    "--format",
+    "--width",
+    "--height",
+    "--scene",
+    "--dump-scene",
+    "-s",
+    "--samples",
+    "--pattern",
+    "--filter",
+    "--seed",
+    "--max-samples",
+    "--threshold",
+    "--max-depth",
+    "-j",
+    "--threads",
+    "--tile-size",
+    "--tile-order",
+    "--passes",
+    "--snapshot-every",
+    "--checkpoint",
+    "--checkpoint-every",
+];
+
+pub struct Options {
+    pub settings: Settings,
+    pub output: String,
+    pub format: ImageFormat,
+    pub scene: Option<String>,
+    pub dump_scene: Option<String>,
+    pub quiet: bool,
+    pub passes: u32,
+    pub snapshot_every: u32,
+    pub checkpoint: String,
+    /// How often to save a checkpoint; `None` unless `--checkpoint-every`
+    /// or `--resume` was given.
+    pub checkpoint_every: Option<Duration>,
+    pub resume: bool,
+}
+
+pub enum Command {
+    Render(Box<Options>),
+    Help,
+}
+
+fn number<T: std::str::FromStr>(flag: &str, value: &str) -> Result<T, String> {
+    value
+        .parse()
+        .map_err(|_| format!("invalid value for {}: {:?}", flag, value))
+}
+
+fn choice<T: Copy>(flag: &str, value: &str, choices: &[(&str, T)]) -> Result<T, String> {
+    choices
+        .iter()
+        .find(|(name, _)| name.eq_ignore_ascii_case(value))
+        .map(|&(_, v)| v)
+        .ok_or_else(|| format!("invalid value for {}: {:?}", flag, value))
+}
+
+/// Parses the arguments after the program name.
+pub fn parse(args: &[String]) -> Result<Command, String> {
+    let mut settings = Settings::default();
+    let mut output = String::from("output.ppm");
+    let mut format = None;
+    let mut scene = None;
+    let mut dump_scene = None;
+    let mut quiet = false;
+    let mut passes = 1;
+    let mut snapshot_every = 1;
+    let mut checkpoint = None;
+    let mut checkpoint_every: Option<f64> = None;
+    let mut resume = false;
+    let mut max_samples = None;
+    let mut threshold = None;
+    let mut per_pixel = None;
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let (flag, inline) = match arg.find('=') {
+            Some(i) if arg.starts_with("--") => (&arg[..i], Some(arg[i + 1..].to_string())),
+            _ => (arg.as_str(), None),
+        };
+        match flag {
+            "-h" | "--help" => return Ok(Command::Help),
+            "-q" | "--quiet" => quiet = true,
+            "--resume" => resume = true,
+            _ if !arg.starts_with('-') || !TAKES_VALUE.contains(&flag) => {
+                return Err(format!("unknown option: {}", arg))
+            }
+            _ => {
+                let value = match inline {
+                    Some(v) => v,
+                    None => args
+                        .next()
+                        .cloned()
+                        .ok_or_else(|| format!("missing value for {}", flag))?,
+                };
+                let v = value.as_str();
+                if PER_PIXEL.contains(&flag) && per_pixel.is_none() {
+                    per_pixel = Some(flag.to_string());
+                }
+                match flag {
+                    "-o" | "--output" => output = value.clone(),
+                    "--format" => {
+                        let formats = [("ppm", ImageFormat::Ppm), ("png", ImageFormat::Png)];
+                        format = Some(choice(flag, v, &formats)?)
+                    }
+                    "--width" => settings.width = number(flag, v)?,
+                    "--height" => settings.height = number(flag, v)?,
+                    "--scene" => scene = Some(value.clone()),
+                    "--dump-scene" => dump_scene = Some(value.clone()),
+                    "-s" | "--samples" => settings.samples = number(flag, v)?,
+                    "--pattern" => {
+                        settings.pattern = choice(
+                            flag,
+                            v,
+                            &[
+                                ("regular", SamplePattern::Regular),
+                                ("jittered", SamplePattern::Jittered),
+                                ("halton", SamplePattern::Halton),
+                                ("sobol", SamplePattern::Sobol),
+                            ],
+                        )?
+                    }
+                    "--filter" => {
+                        settings.filter = choice(
+                            flag,
+                            v,
+                            &[
+                                ("box", Filter::Box),
+                                ("tent", Filter::Tent),
+                                ("gaussian", Filter::Gaussian),
+                                ("mitchell", Filter::Mitchell),
+                            ],
+                        )?
+                    }
+                    "--seed" => settings.seed = number(flag, v)?,
+                    "--max-samples" => max_samples = Some(number(flag, v)?),
+                    "--threshold" => threshold = Some(number(flag, v)?),
+                    "--max-depth" => settings.max_depth = number(flag, v)?,
+                    "-j" | "--threads" => {
+                        let n: usize = number(flag, v)?;
+                        if n > 0 {
+                            settings.threads = n;
+                        }
+                    }
+                    "--tile-size" => settings.tile_size = number(flag, v)?,
+                    "--tile-order" => {
+                        settings.tile_order = choice(
+                            flag,
+                            v,
+                            &[
+                                ("scanline", TileOrder::Scanline),
+                                ("spiral", TileOrder::Spiral),
+                                ("hilbert", TileOrder::Hilbert),
+                            ],
+                        )?
+                    }
+                    "--passes" => passes = number(flag, v)?,
+                    "--snapshot-every" => snapshot_every = number(flag, v)?,
+                    "--checkpoint" => checkpoint = Some(value.clone()),
+                    "--checkpoint-every" => checkpoint_every = Some(number(flag, v)?),
+                    _ => unreachable!("{} is in TAKES_VALUE", flag),
+                }
+            }
+        }
+    }
+
+    if settings.width == 0 || settings.height == 0 {
+        return Err(String::from("width and height must be positive"));
+    }
+    if passes == 0 {
+        return Err(String::from("--passes must be at least 1"));
+    }
+    let checkpoint_every = match checkpoint_every {
+        None if !resume => None,
+        seconds => Some(
+            Duration::try_from_secs_f64(seconds.unwrap_or(30.)).map_err(|_| {
+                String::from("--checkpoint-every must be a non-negative number of seconds")
+            })?,
+        ),
+    };
+    if threshold.is_some() && max_samples.is_none() {
+        return Err(String::from("--threshold needs --max-samples"));
+    }
+    if let Some(flag) = per_pixel.filter(|_| passes > 1) {
+        return Err(format!("{} cannot be combined with --passes", flag));
+    }
+    settings.adaptive = max_samples.map(|max_samples| Adaptive {
+        max_samples,
+        threshold: threshold.unwrap_or(0.01),
+    });
+    Ok(Command::Render(Box::new(Options {
+        settings,
+        format: format.unwrap_or_else(|| ImageFormat::from_path(&output)),
+        checkpoint: checkpoint.unwrap_or_else(|| format!("{}.ckpt", output)),
+        output,
+        scene,
+        dump_scene,
+        quiet,
+        passes,
+        snapshot_every: snapshot_every.max(1),
+        checkpoint_every,
+        resume,
+    })))
+}
+
+#[cfg(test)]
+mod cli_tests {
+    use super::*;
+
+    fn run(args: &[&str]) -> Result<Options, String> {
+        let args: Vec<String> = args.iter().map(|s| s.to_string()).collect();
+        match parse(&args)? {
+            Command::Render(o) => Ok(*o),
+            Command::Help => Err(String::from("help")),
+        }
+    }
+
+    #[test]
+    fn defaults() {
+        let o = run(&[]).expect("valid");
+        assert_eq!(o.output, "output.ppm");
+        assert_eq!(o.format, ImageFormat::Ppm);
+        assert_eq!(o.checkpoint, "output.ppm.ckpt");
+        assert_eq!((o.settings.width, o.settings.height), (200, 200));
+        assert_eq!(o.settings.samples, 1);
+        assert!(o.settings.threads >= 1);
+        assert!(!o.quiet && !o.resume);
+        assert_eq!(o.passes, 1);
+        assert!(o.scene.is_none() && o.dump_scene.is_none());
+        assert_eq!(o.checkpoint_every, None);
+    }
+
+    #[test]
+    fn flags() {
+        let o = run(&[
+            "-o",
+            "out.png",
+            "--width=640",
+            "--height",
+            "480",
+            "-s",
+            "16",
+            "-j",
+            "3",
+            "--max-depth",
+            "2",
+            "--scene",
+            "room.scene",
+            "-q",
+            "--pattern",
+            "Sobol",
+            "--filter=mitchell",
+            "--tile-order",
+            "hilbert",
+            "--max-samples",
+            "64",
+            "--resume",
+        ])
+        .expect("valid");
+        assert_eq!(o.format, ImageFormat::Png);
+        assert_eq!((o.settings.width, o.settings.height), (640, 480));
+        assert_eq!(o.settings.samples, 16);
+        assert_eq!(o.settings.threads, 3);
+        assert_eq!(o.settings.max_depth, 2);
+        assert_eq!(o.scene.as_deref(), Some("room.scene"));
+        assert!(o.quiet && o.resume);
+        assert_eq!(o.settings.pattern, SamplePattern::Sobol);
+        assert_eq!(o.settings.filter, Filter::Mitchell);
+        assert_eq!(o.settings.tile_order, TileOrder::Hilbert);
+        assert_eq!(
+            o.settings.adaptive,
+            Some(Adaptive {
+                max_samples: 64,
+                threshold: 0.01
+            })
+        );
+        // --resume keeps checkpointing at the default interval
+        assert_eq!(o.checkpoint_every, Some(Duration::from_secs(30)));
+        let o = run(&["--passes", "8", "--filter", "tent"]).expect("valid");
+        assert_eq!(o.passes, 8);
+        let o = run(&["--checkpoint-every", "5"]).expect("valid");
+        assert_eq!(o.checkpoint_every, Some(Duration::from_secs(5)));
+        let o = run(&["--dump-scene=scene.json"]).expect("valid");
+        assert_eq!(o.dump_scene.as_deref(), Some("scene.json"));
+        let o = run(&["-o", "x.png", "--format", "ppm"]).expect("valid");
+        assert_eq!(o.format, ImageFormat::Ppm);
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(run(&["--help"]).err().as_deref(), Some("help"));
+        assert_eq!(run(&["-q", "-h"]).err().as_deref(), Some("help"));
+        assert_eq!(
+            run(&["--bogus", "1"]).err().as_deref(),
+            Some("unknown option: --bogus")
+        );
+        assert!(run(&["stray"]).is_err());
+        assert!(run(&["--width"]).is_err());
+        assert!(run(&["--width", "wide"]).is_err());
+        assert!(run(&["--width", "0"]).is_err());
+        assert!(run(&["--filter", "lanczos"]).is_err());
+        assert!(run(&["--passes", "0"]).is_err());
+        assert!(run(&["--checkpoint-every=-1"]).is_err());
+        assert!(run(&["--max-depth", "-1"]).is_err());
+        assert_eq!(
+            run(&["--threshold", "0.1"]).err().as_deref(),
+            Some("--threshold needs --max-samples")
+        );
+        let o = run(&["--max-samples=32", "--threshold=0.1"]).expect("valid");
+        assert_eq!(o.settings.adaptive.map(|a| a.threshold), Some(0.1));
+
+        // progressive passes pick their own samples
+        assert_eq!(
+            run(&["--max-samples", "64", "--passes", "8"])
+                .err()
+                .as_deref(),
+            Some("--max-samples cannot be combined with --passes")
+        );
+        assert!(run(&["--passes=2", "-s", "4"]).is_err());
+        assert!(run(&["--pattern", "sobol", "--passes", "2"]).is_err());
+        assert!(run(&["--pattern", "sobol", "--passes", "1"]).is_ok());
+    }
+}
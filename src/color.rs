@@ -1,10 +1,10 @@
-use self::super::equals;
+use self::super::{equals, Float};
 
 #[derive(Default, Clone)]
 pub struct Color {
-    pub r: f32,
-    pub g: f32,
-    pub b: f32,
+    pub r: Float,
+    pub g: Float,
+    pub b: Float,
 }
 
 impl PartialEq for Color {
@@ -28,7 +28,7 @@ impl Color {
             b: self.b - other.b,
         }
     }
-    pub fn multiply(&self, k: f32) -> Color {
+    pub fn multiply(&self, k: Float) -> Color {
         Color {
             r: k * self.r,
             g: k * self.g,
@@ -0,0 +1,166 @@
+use crate::canvas::Canvas;
+use crate::color::Color;
+use crate::Float;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Comparison {
+    pub rmse: Float,
+    /// Peak signal-to-noise ratio in dB for a peak value of 1.0; infinite
+    /// when the images are identical.
+    pub psnr: Float,
+    pub max_error: Float,
+    /// Pixels where any channel differs by more than the tolerance.
+    pub differing_pixels: u64,
+}
+
+fn channel_errors(a: &Color, b: &Color) -> [Float; 3] {
+    [(a.r - b.r).abs(), (a.g - b.g).abs(), (a.b - b.b).abs()]
+}
+
+fn check_dims(a: &Canvas, b: &Canvas) -> Result<(), String> {
+    if a.width != b.width || a.height != b.height {
+        Err(format!(
+            "size mismatch: {}x{} vs {}x{}",
+            a.width, a.height, b.width, b.height
+        ))
+    } else {
+        Ok(())
+    }
+}
+
+// errors are summed in f64 even when `Float` is f32
+#[cfg_attr(feature = "f64", allow(clippy::unnecessary_cast))]
+pub fn compare(a: &Canvas, b: &Canvas, tolerance: Float) -> Result<Comparison, String> {
+    check_dims(a, b)?;
+    let mut sum_sq = 0.;
+    let mut max_error: Float = 0.;
+    let mut differing_pixels = 0;
+    for ((_, _, ca), (_, _, cb)) in a.pixels().zip(b.pixels()) {
+        let errors = channel_errors(ca, cb);
+        let worst = errors.iter().cloned().fold(0., Float::max);
+        if worst > tolerance {
+            differing_pixels += 1;
+        }
+        max_error = max_error.max(worst);
+        sum_sq += errors.iter().map(|e| (e * e) as f64).sum::<f64>();
+    }
+    let samples = (a.width * a.height * 3).max(1) as f64;
+    let rmse = (sum_sq / samples).sqrt() as Float;
+    Ok(Comparison {
+        rmse,
+        psnr: -20. * rmse.log10(),
+        max_error,
+        differing_pixels,
+    })
+}
+
+/// Renders `a` as dim grey with every pixel that differs from `b` by more
+/// than `tolerance` painted solid red.
+pub fn diff_canvas(a: &Canvas, b: &Canvas, tolerance: Float) -> Result<Canvas, String> {
+    check_dims(a, b)?;
+    let mut out = Canvas::new(a.width, a.height);
+    let pairs = a.pixels().zip(b.pixels());
+    for ((_, _, p), ((_, _, ca), (_, _, cb))) in out.pixels_mut().zip(pairs) {
+        let worst = channel_errors(ca, cb).iter().cloned().fold(0., Float::max);
+        *p = if worst > tolerance {
+            Color {
+                r: 1.,
+                g: 0.,
+                b: 0.,
+            }
+        } else {
+            let grey = 0.25 * (ca.r + ca.g + ca.b) / 3.;
+            Color {
+                r: grey,
+                g: grey,
+                b: grey,
+            }
+        };
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod compare_tests {
+    use super::*;
+    use crate::render::{render, Settings};
+    use crate::scene;
+    use indicatif::ProgressBar;
+
+    /// Compares `canvas` against the PPM stored at `path`. Setting the
+    /// `UPDATE_REFERENCE` environment variable rewrites the reference instead,
+    /// and a failed comparison leaves a `.diff.ppm` next to it.
+    fn compare_to_reference(
+        canvas: &Canvas,
+        path: &str,
+        tolerance: Float,
+    ) -> Result<Comparison, String> {
+        if std::env::var_os("UPDATE_REFERENCE").is_some() {
+            std::fs::write(path, canvas.to_ppm()).map_err(|e| format!("{}: {}", path, e))?;
+        }
+        let reference = std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+        let reference = Canvas::from_ppm(&reference).map_err(|e| format!("{}: {}", path, e))?;
+        let result = compare(canvas, &reference, tolerance)?;
+        if result.differing_pixels > 0 {
+            let diff = diff_canvas(canvas, &reference, tolerance)?;
+            let diff_path = format!("{}.diff.ppm", path.trim_end_matches(".ppm"));
+            std::fs::write(&diff_path, diff.to_ppm())
+                .map_err(|e| format!("{}: {}", diff_path, e))?;
+        }
+        Ok(result)
+    }
+
+    fn red(r: Float) -> Color {
+        Color { r, g: 0., b: 0. }
+    }
+
+    #[test]
+    fn identical() {
+        let a = Canvas::new(4, 4);
+        let c = compare(&a, &Canvas::new(4, 4), 0.).expect("same size");
+        assert_eq!(c.rmse, 0.);
+        assert!(c.psnr.is_infinite());
+        assert_eq!(c.differing_pixels, 0);
+    }
+
+    #[test]
+    fn metrics() {
+        let a = Canvas::new(2, 2);
+        let mut b = Canvas::new(2, 2);
+        b.set(0, 0, red(0.6)).expect("in bounds");
+        b.set(1, 1, red(0.01)).expect("in bounds");
+        let c = compare(&a, &b, 0.05).expect("same size");
+        assert!(crate::equals(c.max_error, 0.6));
+        assert_eq!(c.differing_pixels, 1);
+        let rmse = ((0.36 as Float + 0.0001) / 12.).sqrt();
+        assert!(crate::equals(c.rmse, rmse));
+        assert!(crate::equals(c.psnr, -20. * rmse.log10()));
+        assert!(compare(&a, &Canvas::new(3, 2), 0.).is_err());
+    }
+
+    #[test]
+    fn diff() {
+        let a = Canvas::new(2, 1);
+        let mut b = Canvas::new(2, 1);
+        b.set(1, 0, red(0.5)).expect("in bounds");
+        let d = diff_canvas(&a, &b, 0.1).expect("same size");
+        assert!(d.get(0, 0) == Some(Color::default()));
+        assert!(d.get(1, 0) == Some(red(1.)));
+    }
+
+    #[test]
+    fn golden_sphere() {
+        const REFERENCE: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/reference/sphere.ppm");
+        // what `raytrace --width 16 --height 16` renders
+        let settings = Settings {
+            width: 16,
+            height: 16,
+            threads: 1,
+            ..Settings::default()
+        };
+        let frame = render(&scene::demo(), &settings, &ProgressBar::hidden());
+        let result =
+            compare_to_reference(&frame.beauty, REFERENCE, 0.01).expect("reference readable");
+        assert_eq!(result.differing_pixels, 0);
+    }
+}
@@ -0,0 +1,210 @@
+//! Arithmetic on numbers and named values: `+ - * /`, unary minus,
+//! parentheses and the constant `pi`. Names may contain `-` between
+//! letters, as in `column-spacing`, so subtraction of a name needs spaces:
+//! `width - gap`.
+
+use crate::Float;
+
+/// Deepest nesting of parentheses and unary signs, so that a hostile
+/// scene file gets an error rather than overflowing the stack.
+const MAX_DEPTH: usize = 256;
+
+struct Parser<'a, F> {
+    text: &'a str,
+    pos: usize,
+    depth: usize,
+    lookup: F,
+}
+
+#[cfg_attr(feature = "f64", allow(clippy::useless_conversion))]
+impl<F: Fn(&str) -> Option<Float>> Parser<'_, F> {
+    fn skip_space(&mut self) {
+        let rest = &self.text[self.pos..];
+        self.pos += rest.len() - rest.trim_start().len();
+    }
+    fn peek(&mut self) -> Option<char> {
+        self.skip_space();
+        self.text[self.pos..].chars().next()
+    }
+    fn expr(&mut self) -> Result<f64, String> {
+        let mut v = self.term()?;
+        while let Some(op) = self.peek().filter(|c| *c == '+' || *c == '-') {
+            self.pos += 1;
+            let rhs = self.term()?;
+            v = if op == '+' { v + rhs } else { v - rhs };
+        }
+        Ok(v)
+    }
+    fn term(&mut self) -> Result<f64, String> {
+        let mut v = self.unary()?;
+        while let Some(op) = self.peek().filter(|c| *c == '*' || *c == '/') {
+            self.pos += 1;
+            let rhs = self.unary()?;
+            if op == '/' && rhs == 0. {
+                return Err(String::from("division by zero"));
+            }
+            v = if op == '*' { v * rhs } else { v / rhs };
+        }
+        Ok(v)
+    }
+    // every nested `(` and sign passes through here
+    fn unary(&mut self) -> Result<f64, String> {
+        self.depth += 1;
+        if self.depth > MAX_DEPTH {
+            return Err(String::from("expression is nested too deeply"));
+        }
+        let v = match self.peek() {
+            Some('-') => {
+                self.pos += 1;
+                self.unary().map(|v| -v)
+            }
+            Some('+') => {
+                self.pos += 1;
+                self.unary()
+            }
+            _ => self.atom(),
+        };
+        self.depth -= 1;
+        v
+    }
+    fn atom(&mut self) -> Result<f64, String> {
+        let next = self.peek();
+        let rest = &self.text[self.pos..];
+        match next {
+            Some('(') => {
+                self.pos += 1;
+                let v = self.expr()?;
+                if self.peek() != Some(')') {
+                    return Err(String::from("missing `)`"));
+                }
+                self.pos += 1;
+                Ok(v)
+            }
+            Some(c) if c.is_ascii_digit() || c == '.' => {
+                let len = rest
+                    .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+                    .unwrap_or(rest.len());
+                // exponents: 1e-3, 2.5E4
+                let mut end = len;
+                let exp = &rest[len..];
+                if exp.starts_with(['e', 'E']) {
+                    let digits = exp[1..].trim_start_matches(['+', '-']);
+                    let n = digits.len()
+                        - digits
+                            .trim_start_matches(|c: char| c.is_ascii_digit())
+                            .len();
+                    if n > 0 {
+                        end = len + (exp.len() - digits.len()) + n;
+                    }
+                }
+                let s = &rest[..end];
+                self.pos += end;
+                s.parse().map_err(|_| format!("invalid number `{}`", s))
+            }
+            Some(c) if c.is_ascii_alphabetic() || c == '_' => {
+                let bytes = rest.as_bytes();
+                let mut end = 0;
+                while end < bytes.len() {
+                    let b = bytes[end];
+                    let hyphen =
+                        b == b'-' && end + 1 < bytes.len() && bytes[end + 1].is_ascii_alphabetic();
+                    if b.is_ascii_alphanumeric() || b == b'_' || hyphen {
+                        end += 1;
+                    } else {
+                        break;
+                    }
+                }
+                let name = &rest[..end];
+                self.pos += end;
+                if name == "pi" {
+                    return Ok(std::f64::consts::PI);
+                }
+                (self.lookup)(name)
+                    .map(f64::from)
+                    .ok_or_else(|| format!("unknown variable `{}`", name))
+            }
+            Some(c) => Err(format!("unexpected `{}`", c)),
+            None => Err(String::from("expected a number")),
+        }
+    }
+}
+
+/// Evaluates `text`, resolving names other than `pi` with `lookup`.
+/// Arithmetic is done in f64 whatever `Float` is.
+pub fn eval<F: Fn(&str) -> Option<Float>>(text: &str, lookup: F) -> Result<Float, String> {
+    let mut p = Parser {
+        text,
+        pos: 0,
+        depth: 0,
+        lookup,
+    };
+    let v = p.expr()? as Float;
+    if let Some(c) = p.peek() {
+        return Err(format!("unexpected `{}`", c));
+    }
+    if !v.is_finite() {
+        return Err(format!("`{}` is not a finite number", text));
+    }
+    Ok(v)
+}
+
+#[cfg(test)]
+mod expr_tests {
+    use super::*;
+    use crate::consts::PI;
+    use crate::equals;
+
+    fn vars(name: &str) -> Option<Float> {
+        match name {
+            "spacing" => Some(2.5),
+            "column-count" => Some(4.),
+            _ => None,
+        }
+    }
+
+    fn ok(text: &str) -> Float {
+        eval(text, vars).expect("valid expression")
+    }
+
+    #[test]
+    fn arithmetic() {
+        assert_eq!(ok("3"), 3.);
+        assert_eq!(ok("-1.5"), -1.5);
+        assert_eq!(ok("1e-3"), 0.001);
+        assert_eq!(ok("1 + 2 * 3"), 7.);
+        assert_eq!(ok("(1 + 2) * 3"), 9.);
+        assert_eq!(ok("8 / 2 / 2"), 2.);
+        assert_eq!(ok("2 - 3 - 4"), -5.);
+        assert_eq!(ok("--2"), 2.);
+        assert!(equals(ok("pi / 2"), PI / 2.));
+        assert!(equals(ok("-pi/4"), -PI / 4.));
+    }
+
+    #[test]
+    fn names() {
+        assert_eq!(ok("spacing * 2"), 5.);
+        assert_eq!(ok("column-count*spacing"), 10.);
+        assert_eq!(ok("column-count - 1"), 3.);
+        // `-` before a digit is always subtraction
+        assert_eq!(ok("spacing-1"), 1.5);
+    }
+
+    #[test]
+    fn errors() {
+        let e = |text: &str| eval(text, vars).expect_err("invalid expression");
+        assert_eq!(e("width"), "unknown variable `width`");
+        assert_eq!(e("1 / 0"), "division by zero");
+        assert_eq!(e("(1 + 2"), "missing `)`");
+        assert_eq!(e("1 2"), "unexpected `2`");
+        assert_eq!(e(""), "expected a number");
+        assert_eq!(e("1.2.3"), "invalid number `1.2.3`");
+        assert_eq!(e("1e400"), "`1e400` is not a finite number");
+
+        let nested = |n: usize| format!("{}1{}", "(".repeat(n), ")".repeat(n));
+        assert_eq!(ok(&nested(200)), 1.);
+        assert_eq!(e(&nested(100_000)), "expression is nested too deeply");
+        assert_eq!(e(&"-".repeat(100_000)), "expression is nested too deeply");
+        // depth is nesting, not length
+        assert_eq!(ok(&vec!["(1)"; 1000].join(" + ")), 1000.);
+    }
+}
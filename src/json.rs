@@ -0,0 +1,116 @@
+//! Canonical JSON for a `yaml::Node` tree: mapping keys sorted, two-space
+//! indentation, lists of scalars on one line. Scalars that read as finite
+//! numbers are written as numbers, everything else as strings.
+
+use crate::yaml::{Node, Value};
+
+fn string(s: &str, out: &mut String) {
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+fn scalar(s: &str, out: &mut String) {
+    match s.parse::<f64>() {
+        Ok(v) if v.is_finite() && !s.starts_with(['+', '.']) && !s.ends_with('.') => {
+            // normalise spellings like `1e3` or `-0`
+            out.push_str(&v.to_string())
+        }
+        _ => string(s, out),
+    }
+}
+
+fn write(node: &Node, indent: usize, out: &mut String) {
+    let pad = " ".repeat(indent + 2);
+    match &node.value {
+        Value::Scalar(s) => scalar(s, out),
+        Value::List(items) if items.is_empty() => out.push_str("[]"),
+        Value::List(items) if items.iter().all(|n| matches!(n.value, Value::Scalar(_))) => {
+            out.push('[');
+            for (i, item) in items.iter().enumerate() {
+                if i > 0 {
+                    out.push_str(", ");
+                }
+                write(item, indent, out);
+            }
+            out.push(']');
+        }
+        Value::List(items) => {
+            out.push_str("[\n");
+            for (i, item) in items.iter().enumerate() {
+                out.push_str(&pad);
+                write(item, indent + 2, out);
+                out.push_str(if i + 1 < items.len() { ",\n" } else { "\n" });
+            }
+            out.push_str(&" ".repeat(indent));
+            out.push(']');
+        }
+        Value::Map(entries) if entries.is_empty() => out.push_str("{}"),
+        Value::Map(entries) => {
+            let mut sorted: Vec<&(String, Node)> = entries.iter().collect();
+            sorted.sort_by(|a, b| a.0.cmp(&b.0));
+            out.push_str("{\n");
+            for (i, (key, value)) in sorted.iter().enumerate() {
+                out.push_str(&pad);
+                string(key, out);
+                out.push_str(": ");
+                write(value, indent + 2, out);
+                out.push_str(if i + 1 < sorted.len() { ",\n" } else { "\n" });
+            }
+            out.push_str(&" ".repeat(indent));
+            out.push('}');
+        }
+    }
+}
+
+pub fn to_string(node: &Node) -> String {
+    let mut out = String::new();
+    write(node, 0, &mut out);
+    out.push('\n');
+    out
+}
+
+#[cfg(test)]
+mod json_tests {
+    use super::*;
+    use crate::yaml;
+
+    #[test]
+    fn canonical() {
+        let node = yaml::parse(
+            r#"
+- name: 'say "hi"'
+  at: [1e3, -0, 0.5, x]
+  b:
+    - [1]
+    - []
+  a: 2.50
+"#,
+        )
+        .expect("valid");
+        assert_eq!(
+            to_string(&node),
+            r#"[
+  {
+    "a": 2.5,
+    "at": [1000, -0, 0.5, "x"],
+    "b": [
+      [1],
+      []
+    ],
+    "name": "say \"hi\""
+  }
+]
+"#
+        );
+    }
+}
@@ -0,0 +1,45 @@
+pub mod canvas;
+pub mod checkpoint;
+pub mod cli;
+pub mod color;
+pub mod compare;
+pub mod expr;
+pub mod json;
+pub mod light;
+pub mod material;
+pub mod matrix;
+pub mod pattern;
+pub mod png;
+pub mod quaternion;
+pub mod ray;
+pub mod render;
+pub mod sampling;
+pub mod scene;
+pub mod shapes;
+pub mod simd;
+pub mod tiles;
+pub mod transform;
+pub mod tuple;
+pub mod yaml;
+
+/// Scalar type of the math core; `f64` with the `f64` feature.
+#[cfg(not(feature = "f64"))]
+pub type Float = f32;
+#[cfg(feature = "f64")]
+pub type Float = f64;
+
+/// Constants like `PI` at `Float` precision.
+#[cfg(not(feature = "f64"))]
+pub use std::f32::consts;
+#[cfg(feature = "f64")]
+pub use std::f64::consts;
+
+/// Tolerance for `equals`, and for everything compared with it.
+#[cfg(not(feature = "f64"))]
+pub const EPSILON: Float = 1e-4;
+#[cfg(feature = "f64")]
+pub const EPSILON: Float = 1e-8;
+
+pub const fn equals(a: Float, b: Float) -> bool {
+    a - b < EPSILON && b - a < EPSILON
+}
@@ -0,0 +1,102 @@
+use crate::color::Color;
+use crate::material::Material;
+use crate::tuple::{Normal3, Point3, Vector3};
+
+#[derive(Clone)]
+pub struct PointLight {
+    pub position: Point3,
+    pub intensity: Color,
+}
+
+pub fn point_light(position: Point3, intensity: Color) -> PointLight {
+    PointLight {
+        position,
+        intensity,
+    }
+}
+
+fn reflect(v: Vector3, normal: Normal3) -> Vector3 {
+    v - normal * (2. * normal.dot(v))
+}
+
+/// Phong reflection of `light` off a surface of color `surface` at
+/// `position`. `eye` and `normal` are unit vectors pointing away from the
+/// surface. Shadows are not considered.
+pub fn lighting(
+    material: &Material,
+    surface: &Color,
+    light: &PointLight,
+    position: &Point3,
+    eye: &Vector3,
+    normal: &Normal3,
+) -> Color {
+    let effective = surface.multiply_color(light.intensity.clone());
+    let ambient = effective.multiply(material.ambient);
+    let to_light = (light.position - *position).unit();
+    let light_dot_normal = normal.dot(to_light);
+    if light_dot_normal < 0. {
+        return ambient;
+    }
+    let diffuse = effective.multiply(material.diffuse * light_dot_normal);
+    let reflect_dot_eye = reflect(-to_light, *normal).dot(*eye);
+    if reflect_dot_eye <= 0. {
+        return ambient.add(diffuse);
+    }
+    let specular = light
+        .intensity
+        .multiply(material.specular * reflect_dot_eye.powf(material.shininess));
+    ambient.add(diffuse).add(specular)
+}
+
+#[cfg(test)]
+mod light_tests {
+    use super::*;
+    use crate::consts::FRAC_1_SQRT_2;
+    use crate::Float;
+
+    fn white() -> Color {
+        Color {
+            r: 1.,
+            g: 1.,
+            b: 1.,
+        }
+    }
+
+    fn light_at(x: Float, y: Float, z: Float) -> Color {
+        let m = Material::default();
+        let light = point_light(Point3::new(x, y, z), white());
+        lighting(
+            &m,
+            &m.color,
+            &light,
+            &Point3::new(0., 0., 0.),
+            &Vector3::new(0., 0., -1.),
+            &Normal3::new(0., 0., -1.),
+        )
+    }
+
+    fn gray(v: Float) -> Color {
+        Color { r: v, g: v, b: v }
+    }
+
+    #[test]
+    fn phong() {
+        assert!(light_at(0., 0., -10.) == gray(1.9));
+        assert!(light_at(0., 10., -10.) == gray(0.1 + 0.9 * FRAC_1_SQRT_2));
+        // light behind the surface leaves only the ambient term
+        assert!(light_at(0., 0., 10.) == gray(0.1));
+
+        let m = Material::default();
+        let k = FRAC_1_SQRT_2;
+        let c = lighting(
+            &m,
+            &m.color,
+            &point_light(Point3::new(0., 10., -10.), white()),
+            &Point3::new(0., 0., 0.),
+            &Vector3::new(0., -k, -k),
+            &Normal3::new(0., 0., -1.),
+        );
+        // ambient, diffuse at 45 degrees and full specular
+        assert!(c == gray(1. + 0.9 * FRAC_1_SQRT_2));
+    }
+}
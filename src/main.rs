@@ -1,62 +1,82 @@
-mod canvas;
-mod color;
-mod matrix;
-mod ray;
-mod shapes;
-mod tuple;
-
-use crate::color::Color;
-use crate::matrix::matrix::translation;
-use crate::ray::ray;
-use crate::shapes::Shape;
-use crate::tuple::Tuple;
-use canvas::Canvas;
 use indicatif::ProgressBar;
-use std::fs::File;
-use std::io::Write;
-
-const EPSILON: f32 = 0.0001;
+use raytrace::checkpoint::{render_checkpointed, Checkpoint};
+use raytrace::cli::{self, Command};
+use raytrace::render::{render, render_progressive, Frame, Snapshots};
+use raytrace::scene;
 
-pub fn equals(a: f32, b: f32) -> bool {
-    a - b < EPSILON && b - a < EPSILON
+fn fail(message: &str) -> ! {
+    eprintln!("error: {}", message);
+    std::process::exit(1)
 }
 
 fn main() {
-    // TODO: determine wall dims from canvas dims
-    let width: u64 = 200;
-    let height: u64 = 200;
-    let mut canvas = Canvas::new(width, height);
-    let mut file = File::create("output.ppm").expect("Cannot open file");
-    let s = shapes::sphere(translation(0., 0., 0.));
-    let camera = Tuple::point(0., 0., -5.);
-    let wall_z = 10.;
-    let wall_width = 7.;
-    let wall_height = 7.;
-    let pixel_size_w = wall_width / width as f32;
-    let half_w = wall_width / 2.;
-    let pixel_size_h = wall_height / height as f32;
-    let half_h = wall_height / 2.;
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let options = match cli::parse(&args) {
+        Ok(Command::Render(options)) => options,
+        Ok(Command::Help) => {
+            print!("{}", cli::USAGE);
+            return;
+        }
+        Err(e) => {
+            eprintln!("error: {}\n\n{}", e, cli::USAGE);
+            std::process::exit(2)
+        }
+    };
+    let settings = &options.settings;
+    let scene = match &options.scene {
+        Some(path) => scene::load(path).unwrap_or_else(|e| fail(&e.to_string())),
+        None => scene::demo(),
+    };
+    if let Some(path) = &options.dump_scene {
+        scene::save(&scene, path)
+            .unwrap_or_else(|e| fail(&format!("cannot write {}: {}", path, e)));
+        return;
+    }
 
-    let bar = ProgressBar::new(width);
-    for x in 0..width {
-        let world_x = -half_w + pixel_size_w * x as f32;
-        for y in 0..height {
-            let world_y = -half_h + pixel_size_h * y as f32;
-            let ray = ray(
-                camera.clone(),
-                Tuple::vector(world_x, world_y, wall_z)
-                    .subtract(camera.clone().as_vector())
-                    .unit(),
-            );
-            let r = if s.intersects(ray).len() > 0 {
-                255.
+    let bar = if options.quiet {
+        ProgressBar::hidden()
+    } else {
+        ProgressBar::new(0)
+    };
+    let save = |canvas: &raytrace::canvas::Canvas| {
+        canvas
+            .save_as(&options.output, options.format)
+            .unwrap_or_else(|e| fail(&format!("cannot write {}: {}", options.output, e)))
+    };
+    let snapshot = |pass: u32, frame: &Frame| {
+        if pass < options.passes && pass % options.snapshot_every.max(1) == 0 {
+            save(&frame.beauty);
+        }
+    };
+    let frame = match options.checkpoint_every {
+        Some(every) => {
+            let checkpoint = if options.resume {
+                Checkpoint::load(&options.checkpoint, &scene, settings, options.passes)
+                    .unwrap_or_else(|e| fail(&format!("cannot resume: {}", e)))
             } else {
-                0.
+                Checkpoint::new(&scene, settings, options.passes)
             };
-            canvas.set(x, y, Color { r, g: 0., b: 0. })
+            render_checkpointed(
+                &scene,
+                settings,
+                checkpoint,
+                &options.checkpoint,
+                every,
+                &bar,
+                snapshot,
+            )
+            .unwrap_or_else(|e| fail(&format!("cannot write checkpoint: {}", e)))
         }
-        bar.inc(1);
-    }
-    file.write(canvas.to_ppm().as_bytes())
-        .expect("write failed");
+        None if options.passes == 1 => render(&scene, settings, &bar),
+        None => render_progressive(
+            &scene,
+            settings,
+            options.passes,
+            &Snapshots::EveryPasses(1),
+            &bar,
+            snapshot,
+        ),
+    };
+    bar.finish_and_clear();
+    save(&frame.beauty);
 }
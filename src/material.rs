@@ -0,0 +1,67 @@
+use crate::color::Color;
+use crate::pattern::Pattern;
+use crate::tuple::Point3;
+use crate::Float;
+use std::sync::atomic::{AtomicIsize, Ordering};
+
+static _MAX_MATERIAL_ID: AtomicIsize = AtomicIsize::new(0);
+
+#[derive(Clone)]
+pub struct Material {
+    pub color: Color,
+    /// Replaces `color` when set.
+    pub pattern: Option<Pattern>,
+    pub ambient: Float,
+    pub diffuse: Float,
+    pub specular: Float,
+    pub shininess: Float,
+    id: isize,
+}
+
+pub fn material(color: Color) -> Material {
+    Material {
+        id: _MAX_MATERIAL_ID.fetch_add(1, Ordering::SeqCst),
+        color,
+        pattern: None,
+        ambient: 0.1,
+        diffuse: 0.9,
+        specular: 0.9,
+        shininess: 200.,
+    }
+}
+
+impl Default for Material {
+    fn default() -> Material {
+        material(Color {
+            r: 1.,
+            g: 1.,
+            b: 1.,
+        })
+    }
+}
+
+impl Material {
+    pub fn id(&self) -> isize {
+        self.id
+    }
+    /// Surface color at a point in the shape's object space.
+    pub fn color_at(&self, object_point: Point3) -> Color {
+        match &self.pattern {
+            Some(p) => p.color_at(object_point),
+            None => self.color.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod material_tests {
+    use super::*;
+
+    #[test]
+    fn unique_ids() {
+        let a = Material::default();
+        let b = material(Color::default());
+        assert_ne!(a.id(), b.id());
+        assert_eq!(a.clone().id(), a.id());
+    }
+}
@@ -1,29 +1,65 @@
+#[allow(clippy::module_inception)]
 pub mod matrix {
-    use crate::equals;
-    use crate::tuple::Tuple;
-    use std::cmp::{max, min};
+    use crate::quaternion::Quaternion;
+    use crate::simd;
+    use crate::tuple::{Normal3, Point3, Tuple, Vector3};
+    use crate::{equals, Float};
+    use std::fmt;
+    use std::ops::{Index, IndexMut, Mul};
 
-    pub type Matrix2 = [[f32; 2]; 2];
-    pub type Matrix3 = [[f32; 3]; 3];
-    pub type Matrix4 = [[f32; 4]; 4];
+    pub type Matrix2 = [[Float; 2]; 2];
+    pub type Matrix3 = [[Float; 3]; 3];
 
-    fn remove_idx<T: Clone>(v: Vec<T>, idx: usize) -> Vec<T> {
-        let mut v = v.to_vec();
-        v.remove(idx);
-        v
+    /// A 4x4 transform, row-major. `==` is approximate, to `EPSILON`.
+    #[derive(Debug, Clone, Copy)]
+    pub struct Matrix4(pub [[Float; 4]; 4]);
+
+    /// An affine transform split into parts, applied scale first, then
+    /// rotation, then translation. A mirroring transform has a negative
+    /// x scale.
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    pub struct Decomposition {
+        pub translation: Vector3,
+        pub rotation: Quaternion,
+        pub scale: Vector3,
+    }
+
+    impl Decomposition {
+        pub fn matrix(&self) -> Matrix4 {
+            let (t, s) = (self.translation, self.scale);
+            translation(t.x, t.y, t.z) * self.rotation.matrix() * scale(s.x, s.y, s.z)
+        }
+    }
+
+    /// `i` clamped to `0..=max`.
+    const fn index(i: i32, max: usize) -> usize {
+        if i < 0 {
+            0
+        } else if i as usize > max {
+            max
+        } else {
+            i as usize
+        }
+    }
+
+    /// Index into the full matrix of row or column `i` of a submatrix
+    /// with `removed` taken out.
+    const fn skip(i: usize, removed: usize) -> usize {
+        if i < removed {
+            i
+        } else {
+            i + 1
+        }
     }
 
-    const I4: Matrix4 = [
-        [1., 0., 0., 0.],
-        [0., 1., 0., 0.],
-        [0., 0., 1., 0.],
-        [0., 0., 0., 1.],
-    ];
+    const fn dot_row(r: [Float; 4], t: &Tuple) -> Float {
+        r[0] * t.x + r[1] * t.y + r[2] * t.z + r[3] * t.w
+    }
 
     pub struct M2 {}
 
     impl M2 {
-        pub fn det(m: Matrix2) -> f32 {
+        pub const fn det(m: Matrix2) -> Float {
             m[0][0] * m[1][1] - m[0][1] * m[1][0]
         }
     }
@@ -31,275 +67,713 @@ pub mod matrix {
     pub struct M3 {}
 
     impl M3 {
-        pub fn sub(m: Matrix3, row: i32, col: i32) -> Matrix2 {
-            let row_i = max(0, min(row, 2)) as usize;
-            let col_j = max(0, min(col, 2)) as usize;
-            let row_vecs = remove_idx(m.to_vec(), row_i);
-            let row_vecs: Vec<Vec<f32>> = row_vecs
-                .iter()
-                .map(|col| remove_idx(col.to_vec(), col_j))
-                .collect();
-            [
-                [row_vecs[0][0], row_vecs[0][1]],
-                [row_vecs[1][0], row_vecs[1][1]],
-            ]
+        pub const fn sub(m: Matrix3, row: i32, col: i32) -> Matrix2 {
+            let (row, col) = (index(row, 2), index(col, 2));
+            let mut out = [[0.; 2]; 2];
+            let mut i = 0;
+            while i < 2 {
+                let mut j = 0;
+                while j < 2 {
+                    out[i][j] = m[skip(i, row)][skip(j, col)];
+                    j += 1;
+                }
+                i += 1;
+            }
+            out
         }
-        pub fn minor(m: Matrix3, row: i32, col: i32) -> f32 {
+        pub const fn minor(m: Matrix3, row: i32, col: i32) -> Float {
             let sub_m = M3::sub(m, row, col);
             M2::det(sub_m)
         }
-        pub fn cofactor(m: Matrix3, row: i32, col: i32) -> f32 {
-            ((row + col) % 2 * -2 + 1) as f32 * M3::minor(m, row, col)
+        pub const fn cofactor(m: Matrix3, row: i32, col: i32) -> Float {
+            ((row + col) % 2 * -2 + 1) as Float * M3::minor(m, row, col)
         }
-        pub fn det(m: Matrix3) -> f32 {
+        pub const fn det(m: Matrix3) -> Float {
             m[0][0] * M3::cofactor(m, 0, 0)
                 + m[0][1] * M3::cofactor(m, 0, 1)
                 + m[0][2] * M3::cofactor(m, 0, 2)
         }
     }
 
-    pub struct M4 {}
+    /// Inverse of `m` by Gauss-Jordan elimination with partial pivoting;
+    /// `None` if a pivot is no bigger than `tolerance`.
+    const fn gauss_jordan(m: [[f64; 4]; 4], tolerance: f64) -> Option<[[f64; 4]; 4]> {
+        // [m | I], reduced to [I | m^-1]
+        let mut a = [[0f64; 8]; 4];
+        let mut i = 0;
+        while i < 4 {
+            let mut j = 0;
+            while j < 4 {
+                a[i][j] = m[i][j];
+                j += 1;
+            }
+            a[i][4 + i] = 1.;
+            i += 1;
+        }
+        let mut col = 0;
+        while col < 4 {
+            let mut pivot = col;
+            let mut r = col + 1;
+            while r < 4 {
+                if a[r][col].abs() > a[pivot][col].abs() {
+                    pivot = r;
+                }
+                r += 1;
+            }
+            if a[pivot][col].abs() <= tolerance {
+                return None;
+            }
+            let row = a[pivot];
+            a[pivot] = a[col];
+            a[col] = row;
+            let p = a[col][col];
+            let mut j = 0;
+            while j < 8 {
+                a[col][j] /= p;
+                j += 1;
+            }
+            let mut r = 0;
+            while r < 4 {
+                let f = a[r][col];
+                if r != col && f != 0. {
+                    let mut j = 0;
+                    while j < 8 {
+                        a[r][j] -= f * a[col][j];
+                        j += 1;
+                    }
+                }
+                r += 1;
+            }
+            col += 1;
+        }
+        let mut out = [[0f64; 4]; 4];
+        let mut i = 0;
+        while i < 4 {
+            let mut j = 0;
+            while j < 4 {
+                out[i][j] = a[i][4 + j];
+                j += 1;
+            }
+            i += 1;
+        }
+        Some(out)
+    }
+
+    impl Matrix4 {
+        pub const IDENTITY: Matrix4 = Matrix4([
+            [1., 0., 0., 0.],
+            [0., 1., 0., 0.],
+            [0., 0., 1., 0.],
+            [0., 0., 0., 1.],
+        ]);
+
+        pub const fn identity() -> Matrix4 {
+            Matrix4::IDENTITY
+        }
 
-    impl M4 {
-        pub fn mm(m1: Matrix4, m2: Matrix4) -> Matrix4 {
+        /// `self * rhs`, usable in const contexts.
+        pub const fn mm(self, rhs: Matrix4) -> Matrix4 {
+            let (a, b) = (self.0, rhs.0);
             let mut m = [[0.0; 4]; 4];
-            for i in 0..4 {
-                for j in 0..4 {
-                    for k in 0..4 {
-                        m[i][j] += m1[i][k] * m2[k][j];
-                    }
+            let mut i = 0;
+            while i < 4 {
+                let mut j = 0;
+                while j < 4 {
+                    m[i][j] = a[i][0] * b[0][j]
+                        + a[i][1] * b[1][j]
+                        + a[i][2] * b[2][j]
+                        + a[i][3] * b[3][j];
+                    j += 1;
                 }
+                i += 1;
             }
-            m
+            Matrix4(m)
         }
 
-        pub fn t(m: Matrix4) -> Matrix4 {
-            [
+        /// `self * t`, usable in const contexts.
+        pub const fn dot_tuple(self, t: Tuple) -> Tuple {
+            let m = self.0;
+            Tuple {
+                x: dot_row(m[0], &t),
+                y: dot_row(m[1], &t),
+                z: dot_row(m[2], &t),
+                w: dot_row(m[3], &t),
+            }
+        }
+
+        /// Carries a normal through the transform whose inverse is `self`,
+        /// by multiplying with the transpose of `self`. The result is not
+        /// normalised.
+        pub const fn transform_normal(self, n: Normal3) -> Normal3 {
+            let m = self.0;
+            Normal3 {
+                x: m[0][0] * n.x + m[1][0] * n.y + m[2][0] * n.z,
+                y: m[0][1] * n.x + m[1][1] * n.y + m[2][1] * n.z,
+                z: m[0][2] * n.x + m[1][2] * n.y + m[2][2] * n.z,
+            }
+        }
+
+        pub const fn transpose(self) -> Matrix4 {
+            let m = self.0;
+            Matrix4([
                 [m[0][0], m[1][0], m[2][0], m[3][0]],
                 [m[0][1], m[1][1], m[2][1], m[3][1]],
                 [m[0][2], m[1][2], m[2][2], m[3][2]],
                 [m[0][3], m[1][3], m[2][3], m[3][3]],
-            ]
-        }
-        pub const I: Matrix4 = I4;
-
-        pub fn sub(m: Matrix4, row: i32, col: i32) -> Matrix3 {
-            let row_i = max(0, min(row, 3)) as usize;
-            let col_j = max(0, min(col, 3)) as usize;
-            let row_vecs = remove_idx(m.to_vec(), row_i);
-            let row_vecs: Vec<Vec<f32>> = row_vecs
-                .iter()
-                .map(|col| remove_idx(col.to_vec(), col_j))
-                .collect();
-            [
-                [row_vecs[0][0], row_vecs[0][1], row_vecs[0][2]],
-                [row_vecs[1][0], row_vecs[1][1], row_vecs[1][2]],
-                [row_vecs[2][0], row_vecs[2][1], row_vecs[2][2]],
-            ]
-        }
-        pub fn minor(m: Matrix4, row: i32, col: i32) -> f32 {
-            let sub_m = M4::sub(m, row, col);
-            M3::det(sub_m)
-        }
-        pub fn cofactor(m: Matrix4, row: i32, col: i32) -> f32 {
-            ((row + col) % 2 * -2 + 1) as f32 * M4::minor(m, row, col)
-        }
-        pub fn det(m: Matrix4) -> f32 {
-            m[0][0] * M4::cofactor(m, 0, 0)
-                + m[0][1] * M4::cofactor(m, 0, 1)
-                + m[0][2] * M4::cofactor(m, 0, 2)
-                + m[0][3] * M4::cofactor(m, 0, 3)
-        }
-        pub fn invertible(m: Matrix4) -> bool {
-            !equals(M4::det(m), 0.)
-        }
-        pub fn invert(m: Matrix4) -> Matrix4 {
-            let mut result: Matrix4 = [[0.; 4]; 4];
-            let det = M4::det(m);
-            for row in 0..4 {
-                for col in 0..4 {
-                    let c = M4::cofactor(m, row, col);
-                    result[col as usize][row as usize] = c / det;
+            ])
+        }
+
+        pub const fn sub(self, row: i32, col: i32) -> Matrix3 {
+            let (row, col) = (index(row, 3), index(col, 3));
+            let mut out = [[0.; 3]; 3];
+            let mut i = 0;
+            while i < 3 {
+                let mut j = 0;
+                while j < 3 {
+                    out[i][j] = self.0[skip(i, row)][skip(j, col)];
+                    j += 1;
                 }
+                i += 1;
             }
-            result
-        }
-        pub fn eq(a: Matrix4, b: Matrix4) -> bool {
-            (0..16)
-                .map(|i| equals(a[i / 4][i % 4], b[i / 4][i % 4]))
-                .all(|x| x)
-        }
-        pub fn dot_tuple(m: Matrix4, t: Tuple) -> Tuple {
-            let r: Vec<f32> = m
-                .iter()
-                .map(|row| row[0] * t.x + row[1] * t.y + row[2] * t.z + row[3] * t.w)
-                .collect();
-            Tuple {
-                x: r[0],
-                y: r[1],
-                z: r[2],
-                w: r[3],
+            out
+        }
+        pub const fn minor(self, row: i32, col: i32) -> Float {
+            M3::det(self.sub(row, col))
+        }
+        pub const fn cofactor(self, row: i32, col: i32) -> Float {
+            ((row + col) % 2 * -2 + 1) as Float * self.minor(row, col)
+        }
+        pub const fn det(self) -> Float {
+            let m = self.0;
+            m[0][0] * self.cofactor(0, 0)
+                + m[0][1] * self.cofactor(0, 1)
+                + m[0][2] * self.cofactor(0, 2)
+                + m[0][3] * self.cofactor(0, 3)
+        }
+        /// Whether `try_inverse` succeeds.
+        pub const fn invertible(self) -> bool {
+            self.try_inverse().is_some()
+        }
+        /// Gauss-Jordan elimination with partial pivoting, carried out in
+        /// f64. `None` when the matrix is singular, or so close to it that the
+        /// inverse would be meaningless at `Float` precision.
+        ///
+        /// An affine matrix is judged by its 3x3 linear block alone, and its
+        /// translation is inverted separately, so a large translation does
+        /// not make well-scaled axes look degenerate.
+        #[cfg_attr(feature = "f64", allow(clippy::unnecessary_cast))]
+        pub const fn try_inverse(self) -> Option<Matrix4> {
+            let m = self.0;
+            let mut a = [[0f64; 4]; 4];
+            let mut i = 0;
+            while i < 4 {
+                let mut j = 0;
+                while j < 4 {
+                    a[i][j] = m[i][j] as f64;
+                    if !a[i][j].is_finite() {
+                        return None;
+                    }
+                    j += 1;
+                }
+                i += 1;
+            }
+            let affine = a[3][0] == 0. && a[3][1] == 0. && a[3][2] == 0. && a[3][3] == 1.;
+            let n = if affine { 3 } else { 4 };
+            let mut t = [0f64; 3];
+            let mut norm = 0f64;
+            let mut i = 0;
+            while i < n {
+                let mut j = 0;
+                while j < n {
+                    norm = norm.max(a[i][j].abs());
+                    j += 1;
+                }
+                if affine {
+                    t[i] = a[i][3];
+                    a[i][3] = 0.;
+                }
+                i += 1;
+            }
+            let mut inv = match gauss_jordan(a, norm * Float::EPSILON as f64) {
+                Some(inv) => inv,
+                None => return None,
+            };
+            if affine {
+                // [L t]^-1 is [L^-1 -L^-1 t]
+                let mut i = 0;
+                while i < 3 {
+                    inv[i][3] = -(inv[i][0] * t[0] + inv[i][1] * t[1] + inv[i][2] * t[2]);
+                    i += 1;
+                }
+            }
+            let mut out = [[0.; 4]; 4];
+            let mut i = 0;
+            while i < 4 {
+                let mut j = 0;
+                while j < 4 {
+                    out[i][j] = inv[i][j] as Float;
+                    j += 1;
+                }
+                i += 1;
+            }
+            Some(Matrix4(out))
+        }
+        /// Closed-form inverse: the cofactors are assembled from the twelve
+        /// 2x2 determinants of the top and bottom row pairs. Entries are
+        /// not finite when the matrix is singular; see `try_inverse`.
+        pub const fn inverse(self) -> Matrix4 {
+            let a = self.0;
+            let s0 = a[0][0] * a[1][1] - a[1][0] * a[0][1];
+            let s1 = a[0][0] * a[1][2] - a[1][0] * a[0][2];
+            let s2 = a[0][0] * a[1][3] - a[1][0] * a[0][3];
+            let s3 = a[0][1] * a[1][2] - a[1][1] * a[0][2];
+            let s4 = a[0][1] * a[1][3] - a[1][1] * a[0][3];
+            let s5 = a[0][2] * a[1][3] - a[1][2] * a[0][3];
+            let c5 = a[2][2] * a[3][3] - a[3][2] * a[2][3];
+            let c4 = a[2][1] * a[3][3] - a[3][1] * a[2][3];
+            let c3 = a[2][1] * a[3][2] - a[3][1] * a[2][2];
+            let c2 = a[2][0] * a[3][3] - a[3][0] * a[2][3];
+            let c1 = a[2][0] * a[3][2] - a[3][0] * a[2][2];
+            let c0 = a[2][0] * a[3][1] - a[3][0] * a[2][1];
+            let det = s0 * c5 - s1 * c4 + s2 * c3 + s3 * c2 - s4 * c1 + s5 * c0;
+            let inv = 1. / det;
+            Matrix4([
+                [
+                    (a[1][1] * c5 - a[1][2] * c4 + a[1][3] * c3) * inv,
+                    (-a[0][1] * c5 + a[0][2] * c4 - a[0][3] * c3) * inv,
+                    (a[3][1] * s5 - a[3][2] * s4 + a[3][3] * s3) * inv,
+                    (-a[2][1] * s5 + a[2][2] * s4 - a[2][3] * s3) * inv,
+                ],
+                [
+                    (-a[1][0] * c5 + a[1][2] * c2 - a[1][3] * c1) * inv,
+                    (a[0][0] * c5 - a[0][2] * c2 + a[0][3] * c1) * inv,
+                    (-a[3][0] * s5 + a[3][2] * s2 - a[3][3] * s1) * inv,
+                    (a[2][0] * s5 - a[2][2] * s2 + a[2][3] * s1) * inv,
+                ],
+                [
+                    (a[1][0] * c4 - a[1][1] * c2 + a[1][3] * c0) * inv,
+                    (-a[0][0] * c4 + a[0][1] * c2 - a[0][3] * c0) * inv,
+                    (a[3][0] * s4 - a[3][1] * s2 + a[3][3] * s0) * inv,
+                    (-a[2][0] * s4 + a[2][1] * s2 - a[2][3] * s0) * inv,
+                ],
+                [
+                    (-a[1][0] * c3 + a[1][1] * c1 - a[1][2] * c0) * inv,
+                    (a[0][0] * c3 - a[0][1] * c1 + a[0][2] * c0) * inv,
+                    (-a[3][0] * s3 + a[3][1] * s1 - a[3][2] * s0) * inv,
+                    (a[2][0] * s3 - a[2][1] * s1 + a[2][2] * s0) * inv,
+                ],
+            ])
+        }
+        /// Entrywise comparison to `EPSILON`; what `==` uses.
+        pub const fn approx_eq(&self, other: &Matrix4) -> bool {
+            let mut i = 0;
+            while i < 16 {
+                if !equals(self.0[i / 4][i % 4], other.0[i / 4][i % 4]) {
+                    return false;
+                }
+                i += 1;
+            }
+            true
+        }
+        /// Translation, rotation and scale such that `d.matrix() == self`.
+        /// `None` for projective, singular or sheared matrices.
+        pub fn decompose(self) -> Option<Decomposition> {
+            let m = self.0;
+            let affine = [0., 0., 0., 1.];
+            if !(0..4).all(|j| equals(m[3][j], affine[j])) || self.try_inverse().is_none() {
+                return None;
             }
+            let column = |j: usize| Vector3::new(m[0][j], m[1][j], m[2][j]);
+            let axes = [column(0), column(1), column(2)];
+            let mut s = axes.map(Vector3::norm);
+            if axes[0].dot(axes[1].cross(axes[2])) < 0. {
+                s[0] = -s[0];
+            }
+            let [x, y, z] = [0, 1, 2].map(|j| axes[j] / s[j]);
+            if !(equals(x.dot(y), 0.) && equals(x.dot(z), 0.) && equals(y.dot(z), 0.)) {
+                return None;
+            }
+            let rotation = Matrix4([
+                [x.x, y.x, z.x, 0.],
+                [x.y, y.y, z.y, 0.],
+                [x.z, y.z, z.z, 0.],
+                [0., 0., 0., 1.],
+            ]);
+            Some(Decomposition {
+                translation: column(3),
+                rotation: Quaternion::from_rotation(rotation),
+                scale: Vector3::new(s[0], s[1], s[2]),
+            })
+        }
+
+        // Builder steps. Each one is applied after the transform built so
+        // far, so `identity().rotate_x(a).translate(x, y, z)` rotates first.
+
+        pub const fn translate(self, x: Float, y: Float, z: Float) -> Matrix4 {
+            translation(x, y, z).mm(self)
+        }
+        pub const fn scale(self, x: Float, y: Float, z: Float) -> Matrix4 {
+            scale(x, y, z).mm(self)
+        }
+        pub fn rotate_x(self, rad: Float) -> Matrix4 {
+            rotate_x(rad) * self
+        }
+        pub fn rotate_y(self, rad: Float) -> Matrix4 {
+            rotate_y(rad) * self
+        }
+        pub fn rotate_z(self, rad: Float) -> Matrix4 {
+            rotate_z(rad) * self
+        }
+        pub fn rotate(self, q: Quaternion) -> Matrix4 {
+            q.matrix() * self
+        }
+        pub fn rotate_axis(self, axis: Vector3, rad: Float) -> Matrix4 {
+            rotate_axis(axis, rad) * self
+        }
+        pub fn reflect(self, point: Point3, normal: Vector3) -> Matrix4 {
+            reflect(point, normal) * self
+        }
+        pub const fn shear(
+            self,
+            x_y: Float,
+            x_z: Float,
+            y_x: Float,
+            y_z: Float,
+            z_x: Float,
+            z_y: Float,
+        ) -> Matrix4 {
+            shear(x_y, x_z, y_x, y_z, z_x, z_y).mm(self)
+        }
+    }
+
+    impl PartialEq for Matrix4 {
+        fn eq(&self, other: &Matrix4) -> bool {
+            self.approx_eq(other)
+        }
+    }
+
+    // The operators go through `simd`; `mm` and `dot_tuple` stay scalar so
+    // they can run in const contexts. Both round identically.
+
+    impl Mul for Matrix4 {
+        type Output = Matrix4;
+        fn mul(self, rhs: Matrix4) -> Matrix4 {
+            Matrix4(simd::mm(self.0, rhs.0))
+        }
+    }
+
+    impl Mul<Tuple> for Matrix4 {
+        type Output = Tuple;
+        fn mul(self, t: Tuple) -> Tuple {
+            self * &t
         }
     }
 
-    pub fn translation(x: f32, y: f32, z: f32) -> Matrix4 {
-        [
+    impl Mul<&Tuple> for Matrix4 {
+        type Output = Tuple;
+        fn mul(self, t: &Tuple) -> Tuple {
+            let [x, y, z, w] = simd::transform(self.0, [t.x, t.y, t.z, t.w]);
+            Tuple { x, y, z, w }
+        }
+    }
+
+    /// Projective matrices divide through by w.
+    impl Mul<Point3> for Matrix4 {
+        type Output = Point3;
+        fn mul(self, p: Point3) -> Point3 {
+            let [x, y, z, w] = simd::transform(self.0, [p.x, p.y, p.z, 1.]);
+            if w == 1. {
+                Point3::new(x, y, z)
+            } else {
+                Point3::new(x / w, y / w, z / w)
+            }
+        }
+    }
+
+    impl Mul<Vector3> for Matrix4 {
+        type Output = Vector3;
+        fn mul(self, v: Vector3) -> Vector3 {
+            let [x, y, z, _] = simd::transform(self.0, [v.x, v.y, v.z, 0.]);
+            Vector3::new(x, y, z)
+        }
+    }
+
+    impl Index<usize> for Matrix4 {
+        type Output = [Float; 4];
+        fn index(&self, row: usize) -> &[Float; 4] {
+            &self.0[row]
+        }
+    }
+
+    impl IndexMut<usize> for Matrix4 {
+        fn index_mut(&mut self, row: usize) -> &mut [Float; 4] {
+            &mut self.0[row]
+        }
+    }
+
+    impl fmt::Display for Matrix4 {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            for (i, row) in self.0.iter().enumerate() {
+                if i > 0 {
+                    writeln!(f)?;
+                }
+                write!(f, "|")?;
+                for v in row {
+                    match f.precision() {
+                        Some(p) => write!(f, " {:>w$.p$}", v, w = p + 4, p = p)?,
+                        None => write!(f, " {}", v)?,
+                    }
+                }
+                write!(f, " |")?;
+            }
+            Ok(())
+        }
+    }
+
+    pub const fn translation(x: Float, y: Float, z: Float) -> Matrix4 {
+        Matrix4([
             [1., 0., 0., x],
             [0., 1., 0., y],
             [0., 0., 1., z],
             [0., 0., 0., 1.],
-        ]
+        ])
     }
 
-    pub fn scale(x: f32, y: f32, z: f32) -> Matrix4 {
-        [
+    pub const fn scale(x: Float, y: Float, z: Float) -> Matrix4 {
+        Matrix4([
             [x, 0., 0., 0.],
             [0., y, 0., 0.],
             [0., 0., z, 0.],
             [0., 0., 0., 1.],
-        ]
+        ])
     }
 
-    pub fn rotate_x(rad: f32) -> Matrix4 {
-        [
+    pub fn rotate_x(rad: Float) -> Matrix4 {
+        Matrix4([
             [1., 0., 0., 0.],
             [0., rad.cos(), -(rad.sin()), 0.],
             [0., rad.sin(), rad.cos(), 0.],
             [0., 0., 0., 1.],
-        ]
+        ])
     }
 
-    pub fn rotate_y(rad: f32) -> Matrix4 {
-        [
+    pub fn rotate_y(rad: Float) -> Matrix4 {
+        Matrix4([
             [rad.cos(), 0., rad.sin(), 0.],
             [0., 1., 0., 0.],
             [-(rad.sin()), 0., rad.cos(), 0.],
             [0., 0., 0., 1.],
-        ]
+        ])
     }
 
-    pub fn rotate_z(rad: f32) -> Matrix4 {
-        [
+    pub fn rotate_z(rad: Float) -> Matrix4 {
+        Matrix4([
             [rad.cos(), -(rad.sin()), 0., 0.],
             [rad.sin(), rad.cos(), 0., 0.],
             [0., 0., 1., 0.],
             [0., 0., 0., 1.],
-        ]
+        ])
     }
 
-    pub fn shear(x_y: f32, x_z: f32, y_x: f32, y_z: f32, z_x: f32, z_y: f32) -> Matrix4 {
-        [
+    pub const fn shear(
+        x_y: Float,
+        x_z: Float,
+        y_x: Float,
+        y_z: Float,
+        z_x: Float,
+        z_y: Float,
+    ) -> Matrix4 {
+        Matrix4([
             [1., x_y, x_z, 0.],
             [y_x, 1., y_z, 0.],
             [z_x, z_y, 1., 0.],
             [0., 0., 0., 1.],
-        ]
+        ])
     }
 
+    /// A rotation of `rad` about `axis` through the origin, turning the same
+    /// way as `rotate_x` does about x. A zero `axis` gives NaN entries.
+    pub fn rotate_axis(axis: Vector3, rad: Float) -> Matrix4 {
+        let Vector3 { x, y, z } = axis.unit();
+        let (s, c) = rad.sin_cos();
+        let t = 1. - c;
+        Matrix4([
+            [c + x * x * t, x * y * t - z * s, x * z * t + y * s, 0.],
+            [x * y * t + z * s, c + y * y * t, y * z * t - x * s, 0.],
+            [x * z * t - y * s, y * z * t + x * s, c + z * z * t, 0.],
+            [0., 0., 0., 1.],
+        ])
+    }
+
+    /// Mirror image across the plane through `point` perpendicular to
+    /// `normal`. A zero `normal` gives NaN entries.
+    pub fn reflect(point: Point3, normal: Vector3) -> Matrix4 {
+        let Vector3 { x, y, z } = normal.unit();
+        let d = 2. * (point - Point3::ORIGIN).dot(Vector3::new(x, y, z));
+        Matrix4([
+            [1. - 2. * x * x, -2. * x * y, -2. * x * z, d * x],
+            [-2. * x * y, 1. - 2. * y * y, -2. * y * z, d * y],
+            [-2. * x * z, -2. * y * z, 1. - 2. * z * z, d * z],
+            [0., 0., 0., 1.],
+        ])
+    }
+
+    /// The rotation taking `forward` to +z and `up` into the yz plane, as
+    /// the camera sees the world: +x right, +y up, looking down +z. Entries
+    /// are NaN if `forward` is zero or parallel to `up`.
+    pub fn orientation(forward: Vector3, up: Vector3) -> Matrix4 {
+        let forward = forward.unit();
+        let right = up.cross(forward).unit();
+        let up = forward.cross(right);
+        Matrix4([
+            [right.x, right.y, right.z, 0.],
+            [up.x, up.y, up.z, 0.],
+            [forward.x, forward.y, forward.z, 0.],
+            [0., 0., 0., 1.],
+        ])
+    }
+
+    /// World to view space for an eye at `from` looking at `to`; see
+    /// `orientation`. `up` need not be perpendicular to the view, but NaN
+    /// entries result if it is parallel to it or `from == to`.
+    pub fn look_at(from: Point3, to: Point3, up: Vector3) -> Matrix4 {
+        orientation(to - from, up) * translation(-from.x, -from.y, -from.z)
+    }
+
+    /// View space to clip space for a camera with vertical field of view
+    /// `fov_y` and width over height `aspect`. After the divide by w,
+    /// which `Matrix4 * Point3` does, the view fills -1..1 in x and y, and
+    /// depths `near..far` along +z map to -1..1.
+    pub fn perspective(fov_y: Float, aspect: Float, near: Float, far: Float) -> Matrix4 {
+        let f = 1. / (fov_y / 2.).tan();
+        let depth = far - near;
+        Matrix4([
+            [f / aspect, 0., 0., 0.],
+            [0., f, 0., 0.],
+            [0., 0., (far + near) / depth, -2. * far * near / depth],
+            [0., 0., 1., 0.],
+        ])
+    }
+
+    /// Maps the box `left..right`, `bottom..top`, `near..far` of view
+    /// space onto -1..1 on every axis.
+    pub const fn orthographic(
+        left: Float,
+        right: Float,
+        bottom: Float,
+        top: Float,
+        near: Float,
+        far: Float,
+    ) -> Matrix4 {
+        let (w, h, d) = (right - left, top - bottom, far - near);
+        Matrix4([
+            [2. / w, 0., 0., -(right + left) / w],
+            [0., 2. / h, 0., -(top + bottom) / h],
+            [0., 0., 2. / d, -(far + near) / d],
+            [0., 0., 0., 1.],
+        ])
+    }
+
+    /// The product of `ms` applied in order: `ms[0]` first, so
+    /// `chain(vec![a, b, c])` is `c * b * a`. One matrix gives itself back
+    /// and none gives the identity. `TransformStack::then` builds the same
+    /// product a step at a time, along with its inverse.
     pub fn chain(ms: Vec<Matrix4>) -> Matrix4 {
-        if ms.len() == 1 {
-            ms[0]
-        } else {
-            let mut m = M4::I;
-            for i in 0..ms.len() {
-                m = M4::mm(ms[i], m);
-            }
-            m
-        }
+        ms.into_iter().fold(Matrix4::IDENTITY, |m, t| t * m)
     }
 }
 
 #[cfg(test)]
 mod matrix_tests {
     use self::super::matrix::*;
-    use crate::tuple::Tuple;
-    use std::f32::consts::PI;
+    use crate::consts::PI;
+    use crate::tuple::{Normal3, Point3, Tuple, Vector3};
+    use crate::{Float, EPSILON};
 
     #[test]
     fn eq() {
-        let a: Matrix4 = [
+        let a = Matrix4([
             [1., 2., 3., 4.],
             [5., 6., 7., 8.],
             [9., 8., 7., 6.],
             [5., 4., 3., 2.],
-        ];
-        let b: Matrix4 = [
+        ]);
+        let b = Matrix4([
             [1., 2., 3., 4.],
             [5., 6., 7., 8.],
             [9., 8., 7., 6.],
             [5., 4., 3., 2.],
-        ];
+        ]);
         assert_eq!(a, b)
     }
 
     #[test]
     fn dne() {
-        let a: Matrix4 = [
+        let a = Matrix4([
             [1., 2., 3., 4.],
             [5., 6., 7., 8.],
             [9., 8., 7., 6.],
             [5., 4., 3., 2.],
-        ];
-        let b: Matrix4 = [
+        ]);
+        let b = Matrix4([
             [2., 2., 3., 4.],
             [5., 6., 7., 8.],
             [9., 8., 7., 6.],
             [5., 4., 3., 2.],
-        ];
+        ]);
         assert_ne!(a, b)
     }
 
     #[test]
     fn mm4_test() {
-        let a: Matrix4 = [
+        let a = Matrix4([
             [1., 2., 3., 4.],
             [5., 6., 7., 8.],
             [9., 8., 7., 6.],
             [5., 4., 3., 2.],
-        ];
-        let b: Matrix4 = [
+        ]);
+        let b = Matrix4([
             [-2., 1., 2., 3.],
             [3., 2., 1., -1.],
             [4., 3., 6., 5.],
             [1., 2., 7., 8.],
-        ];
-        let c: Matrix4 = [
+        ]);
+        let c = Matrix4([
             [20., 22., 50., 48.],
             [44., 54., 114., 108.],
             [40., 58., 110., 102.],
             [16., 26., 46., 42.],
-        ];
-        assert_eq!(M4::mm(a, b), c)
+        ]);
+        assert_eq!(a * b, c)
     }
 
     #[test]
     fn id_test() {
-        let a: Matrix4 = [
+        let a = Matrix4([
             [1., 2., 3., 4.],
             [5., 6., 7., 8.],
             [9., 8., 7., 6.],
             [5., 4., 3., 2.],
-        ];
-        assert_eq!(a, M4::mm(a, M4::I))
+        ]);
+        assert_eq!(a, a * Matrix4::IDENTITY)
     }
 
     #[test]
     fn transpose() {
-        let a: Matrix4 = [
+        let a = Matrix4([
             [0., 9., 3., 0.],
             [9., 8., 0., 8.],
             [1., 8., 5., 3.],
             [0., 0., 5., 8.],
-        ];
-        let b: Matrix4 = [
+        ]);
+        let b = Matrix4([
             [0., 9., 1., 0.],
             [9., 8., 8., 0.],
             [3., 0., 5., 5.],
             [0., 8., 3., 8.],
-        ];
-        assert_eq!(M4::t(a), b);
-        assert_eq!(M4::t(M4::I), M4::I)
+        ]);
+        assert_eq!(a.transpose(), b);
+        assert_eq!(Matrix4::IDENTITY.transpose(), Matrix4::IDENTITY)
     }
 
     #[test]
@@ -308,25 +782,25 @@ mod matrix_tests {
         assert_eq!(M2::det(a), 17.);
         let b: Matrix3 = [[1., 2., 6.], [-5., 8., -4.], [2., 6., 4.]];
         assert_eq!(M3::det(b), -196.);
-        let c: Matrix4 = [
+        let c = Matrix4([
             [-2., -8., 3., 5.],
             [-3., 1., 7., 3.],
             [1., 2., -9., 6.],
             [-6., 7., 7., -9.],
-        ];
-        assert_eq!(M4::det(c), -4071.);
+        ]);
+        assert_eq!(c.det(), -4071.);
     }
 
     #[test]
     fn sub() {
-        let a: Matrix4 = [
+        let a = Matrix4([
             [0., 9., 3., 0.],
             [9., 8., 0., 8.],
             [1., 8., 5., 3.],
             [0., 0., 5., 8.],
-        ];
+        ]);
         let b: Matrix3 = [[0., 3., 0.], [9., 0., 8.], [0., 5., 8.]];
-        assert_eq!(M4::sub(a, 2, 1), b);
+        assert_eq!(a.sub(2, 1), b);
         let c: Matrix2 = [[9., 0.], [0., 5.]];
         assert_eq!(M3::sub(b, 0, 2), c)
     }
@@ -348,49 +822,366 @@ mod matrix_tests {
 
     #[test]
     fn inverse() {
-        let a: Matrix4 = [
+        let a = Matrix4([
             [0., 9., 3., 0.],
             [9., 8., 0., 8.],
             [1., 8., 5., 3.],
             [0., 0., 5., 8.],
-        ];
-        let b: Matrix4 = [
+        ]);
+        let b = Matrix4([
             [-2., -8., 3., 5.],
             [-3., 1., 7., 3.],
             [1., 2., -9., 6.],
             [-6., 7., 7., -9.],
-        ];
-        let c = M4::mm(a, b);
-        assert!(M4::eq(M4::mm(c, M4::invert(b)), a));
+        ]);
+        let c = a * b;
+        assert_eq!(c * b.inverse(), a);
+    }
+
+    #[test]
+    fn inverse_values() {
+        // the book's values, kept exact as adjugate / determinant
+        let over = |det: Float, adj: [[Float; 4]; 4]| Matrix4(adj.map(|r| r.map(|v| v / det)));
+        let a = Matrix4([
+            [-5., 2., 6., -8.],
+            [1., -5., 1., 8.],
+            [7., 7., -6., -7.],
+            [1., -3., 7., 4.],
+        ]);
+        let expected = over(
+            532.,
+            [
+                [116., 240., 128., -24.],
+                [-430., -775., -236., 277.],
+                [-42., -119., -28., 105.],
+                [-278., -433., -160., 163.],
+            ],
+        );
+        assert_eq!(a.inverse(), expected);
+        let b = Matrix4([
+            [8., -5., 9., 2.],
+            [7., 5., 6., 1.],
+            [-6., 0., 9., 6.],
+            [-3., 0., -9., -4.],
+        ]);
+        let expected = over(
+            -585.,
+            [
+                [90., 90., 165., 315.],
+                [45., -72., -15., -18.],
+                [-210., -210., -255., -540.],
+                [405., 405., 450., 1125.],
+            ],
+        );
+        assert_eq!(b.inverse(), expected);
+        // agrees with the adjugate built from cofactors
+        for m in [a, b].iter() {
+            let det = m.det();
+            for row in 0..4 {
+                for col in 0..4 {
+                    let c = m.cofactor(row, col) / det;
+                    assert!(crate::equals(m.inverse()[col as usize][row as usize], c));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn try_inverse() {
+        // large translations with well-scaled axes are fine
+        let far = translation(1e7, 0., 0.);
+        assert_eq!(far.try_inverse(), Some(translation(-1e7, 0., 0.)));
+        let far_small = translation(1e6, 0., 0.) * scale(0.1, 0.1, 0.1);
+        let inv = far_small.try_inverse().expect("invertible");
+        assert_eq!(inv, scale(10., 10., 10.) * translation(-1e6, 0., 0.));
+        assert_eq!(inv * Point3::new(1e6, 0., 0.), Point3::ORIGIN);
+        assert!(crate::shapes::try_sphere(far_small).is_ok());
+        assert!((translation(1e7, 0., 0.) * scale(1., 0., 1.))
+            .try_inverse()
+            .is_none());
+
+        let a = Matrix4([
+            [-5., 2., 6., -8.],
+            [1., -5., 1., 8.],
+            [7., 7., -6., -7.],
+            [1., -3., 7., 4.],
+        ]);
+        let inv = a.try_inverse().expect("invertible");
+        assert_eq!(inv, a.inverse());
+
+        let singular = Matrix4([
+            [-4., 2., -2., -3.],
+            [9., 6., 2., 6.],
+            [0., -5., 1., -5.],
+            [0., 0., 0., 0.],
+        ]);
+        assert!(!singular.invertible());
+        assert!(singular.try_inverse().is_none());
+        // det is 1e-6, but a small uniform scale is far from singular
+        let small = scale(0.01, 0.01, 0.01);
+        assert!(small.invertible());
+        assert_eq!(small.inverse(), scale(100., 100., 100.));
+        assert!(scale(1., 0., 1.).try_inverse().is_none());
+        assert!(Matrix4([[0.; 4]; 4]).try_inverse().is_none());
+        let mut nan = Matrix4::IDENTITY;
+        nan[1][2] = Float::NAN;
+        assert!(nan.try_inverse().is_none());
+        // rows 2 and 3 nearly equal
+        let mut nearly = Matrix4::IDENTITY;
+        nearly[2] = [0., 1., Float::EPSILON / 4., 0.];
+        nearly[1] = [0., 1., 0., 0.];
+        assert!(nearly.try_inverse().is_none());
+
+        // a zero leading entry needs a row swap
+        let swap = Matrix4([
+            [0., 1., 0., 0.],
+            [1., 0., 0., 0.],
+            [0., 0., 1., 0.],
+            [0., 0., 0., 1.],
+        ]);
+        assert_eq!(swap.try_inverse(), Some(swap));
+        // small but legitimate scales survive
+        let flat = scale(20., 0.001, 20.);
+        assert_eq!(
+            flat.try_inverse().expect("invertible"),
+            scale(0.05, 1000., 0.05)
+        );
+        // heavy shears
+        let sheared = chain(vec![
+            shear(40., 0., 0., 0., 0., 0.),
+            rotate_y(0.3),
+            shear(0., 0., 0., 0., 40., 0.),
+        ]);
+        let inv = sheared.try_inverse().expect("invertible");
+        assert_eq!(sheared * inv, Matrix4::IDENTITY);
+    }
+
+    #[test]
+    fn operators() {
+        let mut m = translation(1., 2., 3.);
+        assert_eq!(m[0][3], 1.);
+        m[0][3] = 4.;
+        assert_eq!(m, translation(4., 2., 3.));
+        // equality is approximate
+        assert_eq!(m, translation(4. + EPSILON / 2., 2., 3.));
+        assert_ne!(m, translation(4. + EPSILON * 2., 2., 3.));
+        let p = Tuple::point(1., 1., 1.);
+        assert!((m * &p).eq(&Tuple::point(5., 3., 4.)));
+        assert!((m * scale(2., 2., 2.) * p).eq(&Tuple::point(6., 4., 5.)));
+        assert_eq!(
+            format!("{}", scale(2., 0.5, 1.)),
+            "| 2 0 0 0 |\n| 0 0.5 0 0 |\n| 0 0 1 0 |\n| 0 0 0 1 |"
+        );
+        assert_eq!(
+            format!("{:.1}", Matrix4::IDENTITY).lines().next(),
+            Some("|   1.0   0.0   0.0   0.0 |")
+        );
+    }
+
+    #[test]
+    fn typed() {
+        let m = translation(5., -3., 2.) * scale(2., 2., 2.);
+        assert_eq!(m * Point3::new(1., 1., 1.), Point3::new(7., -1., 4.));
+        // vectors ignore translation
+        assert_eq!(m * Vector3::new(1., 1., 1.), Vector3::new(2., 2., 2.));
+        // normals stay perpendicular under non-uniform scale
+        let squash = scale(1., 0.5, 1.);
+        let surface = Vector3::new(1., -1., 0.);
+        let n = Normal3::new(1., 1., 0.);
+        assert!(crate::equals(n.dot(surface), 0.));
+        let moved = squash.inverse().transform_normal(n);
+        assert!(crate::equals(moved.dot(squash * surface), 0.));
+        assert_eq!(moved, Normal3::new(1., 2., 0.));
+        // translation does not move normals
+        assert_eq!(translation(-1., -2., -3.).transform_normal(n), n);
+        // w is divided out
+        let mut project = Matrix4::IDENTITY;
+        project[3] = [0., 0., 1., 0.];
+        assert_eq!(project * Point3::new(2., 4., 2.), Point3::new(1., 2., 1.));
+    }
+
+    #[test]
+    fn fluent() {
+        // the chain_test transform, spelled as a builder
+        let t = Matrix4::identity()
+            .rotate_x(PI / 2.)
+            .scale(5., 5., 5.)
+            .translate(10., 5., 7.);
+        assert!((t * Tuple::point(1., 0., 1.)).eq(&Tuple::point(15., 0., 7.)));
+        assert_eq!(
+            t,
+            translation(10., 5., 7.) * scale(5., 5., 5.) * rotate_x(PI / 2.)
+        );
+        assert_eq!(
+            Matrix4::identity()
+                .shear(1., 0., 0., 0., 0., 0.)
+                .rotate_y(0.5)
+                .rotate_z(0.25),
+            chain(vec![
+                shear(1., 0., 0., 0., 0., 0.),
+                rotate_y(0.5),
+                rotate_z(0.25)
+            ])
+        );
+        const C: Matrix4 = Matrix4::identity().scale(2., 2., 2.).translate(1., 0., 0.);
+        assert!((C * Tuple::point(1., 1., 1.)).eq(&Tuple::point(3., 2., 2.)));
+    }
+
+    #[test]
+    fn const_eval() {
+        const M: Matrix4 = translation(1., 2., 3.).mm(scale(2., 2., 2.));
+        const INV: Matrix4 = M.inverse();
+        const DET: Float = M.det();
+        const P: Tuple = M.dot_tuple(Tuple::point(1., 1., 1.));
+        assert_eq!(DET, 8.);
+        assert_eq!(M * INV, Matrix4::IDENTITY);
+        assert!(P.eq(&Tuple::point(3., 4., 5.)));
+        const S: Matrix3 = M.sub(0, 0);
+        assert_eq!(S, [[2., 0., 2.], [0., 2., 3.], [0., 0., 1.]]);
+        // out-of-range indices clamp, as before
+        assert_eq!(M.sub(-1, 9), M.sub(0, 3));
     }
 
     #[test]
     fn translate() {
         let a = translation(5., -3., 2.);
         let t = Tuple::point(-3., 4., 5.);
-        assert!(Tuple::point(2., 1., 7.).eq(&M4::dot_tuple(a, t.clone())));
-        let i_a = M4::invert(a);
-        assert!(Tuple::point(-8., 7., 3.).eq(&M4::dot_tuple(i_a, t)));
+        assert!(Tuple::point(2., 1., 7.).eq(&(a * &t)));
+        let i_a = a.inverse();
+        assert!(Tuple::point(-8., 7., 3.).eq(&(i_a * t)));
         let v = Tuple::vector(-3., 4., 5.);
-        assert!(v.clone().eq(&M4::dot_tuple(a, v)));
+        assert!(v.clone().eq(&(a * v)));
     }
 
     #[test]
     fn scale_test() {
         let a = scale(2., 3., 4.);
         let t = Tuple::point(-4., 6., 8.);
-        assert!(Tuple::point(-8., 18., 32.).eq(&M4::dot_tuple(a, t.clone())));
-        let i_a = M4::invert(a);
-        assert!(Tuple::point(-2., 2., 2.).eq(&M4::dot_tuple(i_a, t)));
+        assert!(Tuple::point(-8., 18., 32.).eq(&(a * &t)));
+        let i_a = a.inverse();
+        assert!(Tuple::point(-2., 2., 2.).eq(&(i_a * t)));
         let v = Tuple::vector(-4., 6., 8.);
-        assert!(Tuple::vector(-8., 18., 32.).eq(&M4::dot_tuple(a, v)));
+        assert!(Tuple::vector(-8., 18., 32.).eq(&(a * v)));
     }
 
     #[test]
     fn reflect() {
         let a = scale(-1., 1., 1.);
         let t = Tuple::point(-4., 6., 8.);
-        assert!(Tuple::point(4., 6., 8.).eq(&M4::dot_tuple(a, t.clone())));
+        assert!(Tuple::point(4., 6., 8.).eq(&(a * &t)));
+    }
+
+    #[test]
+    fn reflect_plane() {
+        // the `reflect` test above hides the constructor's name
+        use self::super::matrix::reflect;
+        let x = Vector3::new(1., 0., 0.);
+        assert_eq!(reflect(Point3::ORIGIN, x * 3.), scale(-1., 1., 1.));
+
+        let floor = reflect(Point3::new(7., 2., -1.), Vector3::new(0., 1., 0.));
+        assert_eq!(floor * Point3::new(1., 5., 3.), Point3::new(1., -1., 3.));
+        assert_eq!(floor * Vector3::new(1., 5., 3.), Vector3::new(1., -5., 3.));
+        assert_eq!(floor * floor, Matrix4::IDENTITY);
+        // the plane x = y swaps x and y
+        let diagonal = reflect(Point3::ORIGIN, Vector3::new(1., -1., 0.));
+        assert_eq!(diagonal * Point3::new(2., 5., 3.), Point3::new(5., 2., 3.));
+        assert!(crate::equals(floor.det(), -1.));
+        assert_eq!(
+            Matrix4::identity().reflect(Point3::new(0., 2., 0.), Vector3::new(0., 1., 0.)),
+            translation(0., 4., 0.) * scale(1., -1., 1.)
+        );
+    }
+
+    #[test]
+    fn rotate_axis_test() {
+        let (x, y, z) = (
+            Vector3::new(1., 0., 0.),
+            Vector3::new(0., 1., 0.),
+            Vector3::new(0., 0., 1.),
+        );
+        assert_eq!(rotate_axis(x, PI / 4.), rotate_x(PI / 4.));
+        assert_eq!(rotate_axis(y * 2., 1.3), rotate_y(1.3));
+        assert_eq!(rotate_axis(z, -0.4), rotate_z(-0.4));
+        assert_eq!(rotate_axis(-z, 0.4), rotate_z(-0.4));
+        assert!(rotate_axis(Vector3::new(0., 0., 0.), 0.4)[0][0].is_nan());
+
+        // a third of a turn about the diagonal cycles the axes
+        let diagonal = Vector3::new(1., 1., 1.);
+        let third = rotate_axis(diagonal, 2. * PI / 3.);
+        assert_eq!(third * x, y);
+        assert_eq!(third * y, z);
+        assert_eq!(third * Point3::new(2., 2., 2.), Point3::new(2., 2., 2.));
+        assert_eq!(
+            Matrix4::identity()
+                .translate(1., 0., 0.)
+                .rotate_axis(diagonal, 2. * PI / 3.),
+            third * translation(1., 0., 0.)
+        );
+    }
+
+    #[test]
+    fn look_at_test() {
+        let up = Vector3::new(0., 1., 0.);
+        let origin = Point3::ORIGIN;
+        // the default view already looks down +z
+        assert_eq!(
+            look_at(origin, Point3::new(0., 0., 1.), up),
+            Matrix4::IDENTITY
+        );
+        assert_eq!(
+            look_at(origin, Point3::new(0., 0., -1.), up),
+            scale(-1., 1., -1.)
+        );
+        assert_eq!(
+            look_at(Point3::new(0., 0., -5.), origin, up),
+            translation(0., 0., 5.)
+        );
+
+        let (from, to) = (Point3::new(1., 3., 2.), Point3::new(4., -2., 8.));
+        let view = look_at(from, to, Vector3::new(1., 1., 0.));
+        assert_eq!(view * from, origin);
+        assert_eq!(view * to, Point3::new(0., 0., (to - from).norm()));
+        assert!(crate::equals(view.det(), 1.));
+        // up stays up, even when not perpendicular to the view
+        let seen = view * Vector3::new(1., 1., 0.);
+        assert!(crate::equals(seen.x, 0.) && seen.y > 0.);
+        assert_eq!(
+            orientation(to - from, Vector3::new(1., 1., 0.)),
+            view * translation(from.x, from.y, from.z)
+        );
+        // no way to tell which way is up
+        assert!(look_at(origin, Point3::new(0., 5., 0.), up)[0][0].is_nan());
+        assert!(look_at(from, from, up)[2][2].is_nan());
+    }
+
+    #[test]
+    fn perspective_test() {
+        let p = perspective(PI / 2., 2., 1., 10.);
+        // near and far planes bound the depth range
+        assert_eq!(p * Point3::new(0., 0., 1.), Point3::new(0., 0., -1.));
+        assert_eq!(p * Point3::new(0., 0., 10.), Point3::new(0., 0., 1.));
+        // a quarter turn of view is one unit up per unit ahead, and the
+        // width is twice the height
+        let edge = p * Point3::new(8., 4., 4.);
+        assert!(crate::equals(edge.x, 1.) && crate::equals(edge.y, 1.));
+        let far_edge = p * Point3::new(-20., -10., 10.);
+        assert_eq!(far_edge, Point3::new(-1., -1., 1.));
+        // w carries the depth before the divide
+        assert!((p * Tuple::point(0., 0., 3.)).w == 3.);
+        assert!(p.invertible());
+    }
+
+    #[test]
+    fn orthographic_test() {
+        const O: Matrix4 = orthographic(-2., 6., -1., 1., 0.5, 10.5);
+        assert_eq!(O * Point3::new(-2., -1., 0.5), Point3::new(-1., -1., -1.));
+        assert_eq!(O * Point3::new(6., 1., 10.5), Point3::new(1., 1., 1.));
+        assert_eq!(O * Point3::new(2., 0., 5.5), Point3::new(0., 0., 0.));
+        // no foreshortening
+        let (near, far) = (O * Point3::new(4., 0.5, 1.), O * Point3::new(4., 0.5, 9.));
+        assert!(near.x == far.x && near.y == far.y);
+        assert_eq!(O * Vector3::new(0., 0., 5.), Vector3::new(0., 0., 1.));
+        assert_eq!(orthographic(-1., 1., -1., 1., -1., 1.), Matrix4::IDENTITY);
     }
 
     #[test]
@@ -398,42 +1189,30 @@ mod matrix_tests {
         let a = Tuple::point(0., 1., 0.);
         let half_quarter = rotate_x(PI / 4.);
         let full_quarter = rotate_x(PI / 2.);
-        let root_2 = (2. as f32).sqrt();
-        assert!(M4::dot_tuple(half_quarter, a.clone()).eq(&Tuple::point(
-            0.,
-            root_2 / 2.,
-            root_2 / 2.
-        )));
-        assert!(M4::dot_tuple(full_quarter, a).eq(&Tuple::point(0., 0., 1.)));
+        let root_2 = crate::consts::SQRT_2;
+        assert!((half_quarter * &a).eq(&Tuple::point(0., root_2 / 2., root_2 / 2.)));
+        assert!((full_quarter * a).eq(&Tuple::point(0., 0., 1.)));
 
         let a = Tuple::point(0., 0., 1.);
         let half_quarter = rotate_y(PI / 4.);
         let full_quarter = rotate_y(PI / 2.);
-        assert!(M4::dot_tuple(half_quarter, a.clone()).eq(&Tuple::point(
-            root_2 / 2.,
-            0.,
-            root_2 / 2.
-        )));
-        assert!(M4::dot_tuple(full_quarter, a).eq(&Tuple::point(1., 0., 0.)));
+        assert!((half_quarter * &a).eq(&Tuple::point(root_2 / 2., 0., root_2 / 2.)));
+        assert!((full_quarter * a).eq(&Tuple::point(1., 0., 0.)));
 
         let a = Tuple::point(0., 1., 0.);
         let half_quarter = rotate_z(PI / 4.);
         let full_quarter = rotate_z(PI / 2.);
-        assert!(M4::dot_tuple(half_quarter, a.clone()).eq(&Tuple::point(
-            -root_2 / 2.,
-            root_2 / 2.,
-            0.
-        )));
-        assert!(M4::dot_tuple(full_quarter, a).eq(&Tuple::point(-1., 0., 0.)));
+        assert!((half_quarter * &a).eq(&Tuple::point(-root_2 / 2., root_2 / 2., 0.)));
+        assert!((full_quarter * a).eq(&Tuple::point(-1., 0., 0.)));
     }
 
     #[test]
     fn shear_test() {
         let a = Tuple::point(2., 3., 4.);
         let s = shear(1., 0., 0., 0., 0., 0.);
-        assert!(M4::dot_tuple(s, a.clone()).eq(&Tuple::point(5., 3., 4.)));
+        assert!((s * &a).eq(&Tuple::point(5., 3., 4.)));
         let s = shear(-1., 0., 0., 2., 0., 0.5);
-        assert!(M4::dot_tuple(s, a).eq(&Tuple::point(-1., 11., 5.5)));
+        assert!((s * a).eq(&Tuple::point(-1., 11., 5.5)));
     }
 
     #[test]
@@ -442,13 +1221,13 @@ mod matrix_tests {
         let a = rotate_x(PI / 2.);
         let b = scale(5., 5., 5.);
         let c = translation(10., 5., 7.);
-        let p2 = M4::dot_tuple(a.clone(), p.clone());
+        let p2 = a * &p;
         assert!(p2.eq(&Tuple::point(1., -1., 0.)));
-        let p3 = M4::dot_tuple(b.clone(), p2);
+        let p3 = b * p2;
         assert!(p3.eq(&Tuple::point(5., -5., 0.)));
-        let p4 = M4::dot_tuple(c.clone(), p3);
+        let p4 = c * p3;
         assert!(p4.eq(&Tuple::point(15., 0., 7.)));
         let t = chain(vec![a, b, c]);
-        assert!(p4.eq(&M4::dot_tuple(t, p)));
+        assert!(p4.eq(&(t * p)));
     }
 }
@@ -0,0 +1,144 @@
+use crate::color::Color;
+use crate::matrix::matrix::Matrix4;
+use crate::shapes::SingularTransform;
+use crate::tuple::Point3;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PatternKind {
+    /// Alternates along x.
+    Stripes,
+    /// Blends from `a` to `b` along x, repeating every unit.
+    Gradient,
+    /// Concentric rings around the y axis.
+    Rings,
+    /// 3D checkerboard of unit cubes.
+    Checkers,
+}
+
+/// Two-color pattern, evaluated in pattern space: object space
+/// transformed by the inverse of `transform()`.
+#[derive(Clone)]
+pub struct Pattern {
+    pub kind: PatternKind,
+    pub a: Color,
+    pub b: Color,
+    transform: Matrix4,
+    inverse: Matrix4,
+}
+
+pub fn pattern(kind: PatternKind, a: Color, b: Color) -> Pattern {
+    Pattern {
+        kind,
+        a,
+        b,
+        transform: Matrix4::IDENTITY,
+        inverse: Matrix4::IDENTITY,
+    }
+}
+
+impl Pattern {
+    /// Panics if `transform` is singular; see `try_with_transform`.
+    pub fn with_transform(self, transform: Matrix4) -> Pattern {
+        match self.try_with_transform(transform) {
+            Ok(p) => p,
+            Err(e) => panic!("{}", e),
+        }
+    }
+    pub fn try_with_transform(mut self, transform: Matrix4) -> Result<Pattern, SingularTransform> {
+        self.inverse = transform.try_inverse().ok_or(SingularTransform {
+            transform: Box::new(transform),
+        })?;
+        self.transform = transform;
+        Ok(self)
+    }
+    pub fn transform(&self) -> Matrix4 {
+        self.transform
+    }
+    /// Color at a point in the object space of the shape being shaded.
+    pub fn color_at(&self, object_point: Point3) -> Color {
+        let p = self.inverse * object_point;
+        let pick = |first: bool| {
+            if first {
+                self.a.clone()
+            } else {
+                self.b.clone()
+            }
+        };
+        match self.kind {
+            PatternKind::Stripes => pick((p.x.floor() as i64).rem_euclid(2) == 0),
+            PatternKind::Gradient => {
+                let f = p.x - p.x.floor();
+                self.a.add(self.b.subtract(self.a.clone()).multiply(f))
+            }
+            PatternKind::Rings => {
+                let r = (p.x * p.x + p.z * p.z).sqrt();
+                pick((r.floor() as i64).rem_euclid(2) == 0)
+            }
+            PatternKind::Checkers => {
+                let sum = p.x.floor() + p.y.floor() + p.z.floor();
+                pick((sum as i64).rem_euclid(2) == 0)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod pattern_tests {
+    use super::*;
+    use crate::matrix::matrix::scale;
+
+    fn black() -> Color {
+        Color::default()
+    }
+
+    fn white() -> Color {
+        Color {
+            r: 1.,
+            g: 1.,
+            b: 1.,
+        }
+    }
+
+    #[test]
+    fn kinds() {
+        let p = pattern(PatternKind::Stripes, white(), black());
+        assert!(p.color_at(Point3::new(0.9, 5., 5.)) == white());
+        assert!(p.color_at(Point3::new(1., 0., 0.)) == black());
+        assert!(p.color_at(Point3::new(-0.1, 0., 0.)) == black());
+
+        let p = pattern(PatternKind::Gradient, white(), black());
+        let c = p.color_at(Point3::new(0.25, 0., 0.));
+        assert!(
+            c == Color {
+                r: 0.75,
+                g: 0.75,
+                b: 0.75
+            }
+        );
+
+        let p = pattern(PatternKind::Rings, white(), black());
+        assert!(p.color_at(Point3::new(0., 0., 0.)) == white());
+        assert!(p.color_at(Point3::new(0.708, 0., 0.708)) == black());
+
+        let p = pattern(PatternKind::Checkers, white(), black());
+        assert!(p.color_at(Point3::new(0.99, 0., 0.)) == white());
+        assert!(p.color_at(Point3::new(0., 0., 1.01)) == black());
+    }
+
+    #[test]
+    fn transformed() {
+        let p = pattern(PatternKind::Stripes, white(), black()).with_transform(scale(2., 2., 2.));
+        assert!(p.color_at(Point3::new(1.5, 0., 0.)) == white());
+        assert!(p.color_at(Point3::new(2.5, 0., 0.)) == black());
+    }
+
+    #[test]
+    fn singular_transform() {
+        let flat = scale(1., 0., 1.);
+        let p = pattern(PatternKind::Rings, white(), black());
+        let e = p.clone().try_with_transform(flat).err().expect("singular");
+        assert_eq!(*e.transform, flat);
+        assert!(p.clone().try_with_transform(scale(2., 2., 2.)).is_ok());
+        assert!(std::panic::catch_unwind(|| p.with_transform(flat)).is_err());
+    }
+}
@@ -0,0 +1,297 @@
+use crate::matrix::matrix::Matrix4;
+use crate::tuple::Vector3;
+use crate::{equals, Float};
+use std::ops::{Add, Mul, Neg};
+
+/// A rotation as a unit quaternion `w + xi + yj + zk`. `q` and `-q` are the
+/// same rotation; `==` compares components, to `EPSILON`.
+#[derive(Debug, Clone, Copy)]
+pub struct Quaternion {
+    pub w: Float,
+    pub x: Float,
+    pub y: Float,
+    pub z: Float,
+}
+
+impl Quaternion {
+    pub const IDENTITY: Quaternion = Quaternion::new(1., 0., 0., 0.);
+
+    pub const fn new(w: Float, x: Float, y: Float, z: Float) -> Quaternion {
+        Quaternion { w, x, y, z }
+    }
+    /// A rotation of `rad` about `axis`, counterclockwise looking down the
+    /// axis towards the origin, as `rotate_x` and friends are.
+    pub fn from_axis_angle(axis: Vector3, rad: Float) -> Quaternion {
+        let a = axis.unit();
+        let (sin, cos) = (rad / 2.).sin_cos();
+        Quaternion::new(cos, a.x * sin, a.y * sin, a.z * sin)
+    }
+    /// Rotates about x, then y, then z: the same rotation as
+    /// `chain(vec![rotate_x(x), rotate_y(y), rotate_z(z)])`.
+    pub fn from_euler(x: Float, y: Float, z: Float) -> Quaternion {
+        let about = |x, y, z, rad| Quaternion::from_axis_angle(Vector3::new(x, y, z), rad);
+        about(0., 0., 1., z) * about(0., 1., 0., y) * about(1., 0., 0., x)
+    }
+    /// The rotation in the upper 3x3 block of `m`, which must be
+    /// orthonormal with determinant 1. Of `q` and `-q`, the one with
+    /// `w >= 0` is returned.
+    pub fn from_rotation(m: Matrix4) -> Quaternion {
+        let m = m.0;
+        let trace = m[0][0] + m[1][1] + m[2][2];
+        let q = if trace > 0. {
+            let s = (trace + 1.).sqrt() * 2.;
+            Quaternion::new(
+                s / 4.,
+                (m[2][1] - m[1][2]) / s,
+                (m[0][2] - m[2][0]) / s,
+                (m[1][0] - m[0][1]) / s,
+            )
+        } else if m[0][0] > m[1][1] && m[0][0] > m[2][2] {
+            let s = (1. + m[0][0] - m[1][1] - m[2][2]).sqrt() * 2.;
+            Quaternion::new(
+                (m[2][1] - m[1][2]) / s,
+                s / 4.,
+                (m[0][1] + m[1][0]) / s,
+                (m[0][2] + m[2][0]) / s,
+            )
+        } else if m[1][1] > m[2][2] {
+            let s = (1. + m[1][1] - m[0][0] - m[2][2]).sqrt() * 2.;
+            Quaternion::new(
+                (m[0][2] - m[2][0]) / s,
+                (m[0][1] + m[1][0]) / s,
+                s / 4.,
+                (m[1][2] + m[2][1]) / s,
+            )
+        } else {
+            let s = (1. + m[2][2] - m[0][0] - m[1][1]).sqrt() * 2.;
+            Quaternion::new(
+                (m[1][0] - m[0][1]) / s,
+                (m[0][2] + m[2][0]) / s,
+                (m[1][2] + m[2][1]) / s,
+                s / 4.,
+            )
+        };
+        if q.w < 0. {
+            -q.unit()
+        } else {
+            q.unit()
+        }
+    }
+    pub fn dot(self, other: Quaternion) -> Float {
+        self.w * other.w + self.x * other.x + self.y * other.y + self.z * other.z
+    }
+    pub fn norm(self) -> Float {
+        self.dot(self).sqrt()
+    }
+    pub fn unit(self) -> Quaternion {
+        self * (1. / self.norm())
+    }
+    /// The inverse rotation, for unit quaternions.
+    pub fn conjugate(self) -> Quaternion {
+        Quaternion::new(self.w, -self.x, -self.y, -self.z)
+    }
+    /// Spherical interpolation from `self` at `t = 0` to `other` at `t = 1`
+    /// at constant angular speed, along the shorter arc.
+    pub fn slerp(self, other: Quaternion, t: Float) -> Quaternion {
+        let (other, cos) = match self.dot(other) {
+            d if d < 0. => (-other, -d),
+            d => (other, d),
+        };
+        if cos > 1. - crate::EPSILON {
+            // nearly parallel; the sine below would vanish
+            return (self * (1. - t) + other * t).unit();
+        }
+        let angle = cos.acos();
+        let sin = angle.sin();
+        self * (((1. - t) * angle).sin() / sin) + other * ((t * angle).sin() / sin)
+    }
+    pub fn matrix(self) -> Matrix4 {
+        let Quaternion { w, x, y, z } = self.unit();
+        Matrix4([
+            [
+                1. - 2. * (y * y + z * z),
+                2. * (x * y - w * z),
+                2. * (x * z + w * y),
+                0.,
+            ],
+            [
+                2. * (x * y + w * z),
+                1. - 2. * (x * x + z * z),
+                2. * (y * z - w * x),
+                0.,
+            ],
+            [
+                2. * (x * z - w * y),
+                2. * (y * z + w * x),
+                1. - 2. * (x * x + y * y),
+                0.,
+            ],
+            [0., 0., 0., 1.],
+        ])
+    }
+}
+
+impl Default for Quaternion {
+    fn default() -> Quaternion {
+        Quaternion::IDENTITY
+    }
+}
+
+impl PartialEq for Quaternion {
+    fn eq(&self, other: &Quaternion) -> bool {
+        equals(self.w, other.w)
+            && equals(self.x, other.x)
+            && equals(self.y, other.y)
+            && equals(self.z, other.z)
+    }
+}
+
+impl From<Quaternion> for Matrix4 {
+    fn from(q: Quaternion) -> Matrix4 {
+        q.matrix()
+    }
+}
+
+/// Composition, like matrices: `a * b` rotates by `b`, then by `a`.
+impl Mul for Quaternion {
+    type Output = Quaternion;
+    fn mul(self, o: Quaternion) -> Quaternion {
+        Quaternion::new(
+            self.w * o.w - self.x * o.x - self.y * o.y - self.z * o.z,
+            self.w * o.x + self.x * o.w + self.y * o.z - self.z * o.y,
+            self.w * o.y - self.x * o.z + self.y * o.w + self.z * o.x,
+            self.w * o.z + self.x * o.y - self.y * o.x + self.z * o.w,
+        )
+    }
+}
+
+impl Mul<Vector3> for Quaternion {
+    type Output = Vector3;
+    fn mul(self, v: Vector3) -> Vector3 {
+        let p = self * Quaternion::new(0., v.x, v.y, v.z) * self.conjugate();
+        Vector3::new(p.x, p.y, p.z)
+    }
+}
+
+impl Mul<Float> for Quaternion {
+    type Output = Quaternion;
+    fn mul(self, k: Float) -> Quaternion {
+        Quaternion::new(self.w * k, self.x * k, self.y * k, self.z * k)
+    }
+}
+
+impl Add for Quaternion {
+    type Output = Quaternion;
+    fn add(self, o: Quaternion) -> Quaternion {
+        Quaternion::new(self.w + o.w, self.x + o.x, self.y + o.y, self.z + o.z)
+    }
+}
+
+impl Neg for Quaternion {
+    type Output = Quaternion;
+    fn neg(self) -> Quaternion {
+        Quaternion::new(-self.w, -self.x, -self.y, -self.z)
+    }
+}
+
+#[cfg(test)]
+mod quaternion_tests {
+    use super::*;
+    use crate::consts::{FRAC_PI_2, FRAC_PI_4, PI};
+    use crate::matrix::matrix::{chain, rotate_x, rotate_y, rotate_z, scale, translation};
+    use crate::tuple::Point3;
+
+    #[test]
+    fn rotations() {
+        let x = Vector3::new(1., 0., 0.);
+        let y = Vector3::new(0., 1., 0.);
+        let z = Vector3::new(0., 0., 1.);
+        let cases = [
+            (x, FRAC_PI_4, rotate_x(FRAC_PI_4)),
+            (y, 1.2, rotate_y(1.2)),
+            (z, -2.5, rotate_z(-2.5)),
+        ];
+        for (axis, rad, m) in cases {
+            let q = Quaternion::from_axis_angle(axis, rad);
+            assert_eq!(q.matrix(), m);
+            assert_eq!(Quaternion::from_rotation(m), q);
+        }
+        // a quarter turn about z takes x to y
+        let q = Quaternion::from_axis_angle(z * 3., FRAC_PI_2);
+        assert_eq!(q * x, y);
+        assert_eq!(
+            Matrix4::from(q) * Point3::new(1., 0., 0.),
+            Point3::new(0., 1., 0.)
+        );
+        // composition matches matrices
+        let a = Quaternion::from_axis_angle(Vector3::new(1., 2., 3.), 0.7);
+        let b = Quaternion::from_axis_angle(Vector3::new(-2., 0., 1.), 2.9);
+        assert_eq!((a * b).matrix(), a.matrix() * b.matrix());
+        assert_eq!(a * a.conjugate(), Quaternion::IDENTITY);
+        // every branch of the matrix conversion, up to sign
+        for q in [a, b, a * b, Quaternion::from_axis_angle(y, PI)] {
+            let back = Quaternion::from_rotation(q.matrix());
+            assert!(back == q || back == -q);
+        }
+    }
+
+    #[test]
+    fn euler() {
+        let q = Quaternion::from_euler(0.3, -1.1, 2.);
+        let m = chain(vec![rotate_x(0.3), rotate_y(-1.1), rotate_z(2.)]);
+        assert_eq!(q.matrix(), m);
+        // gimbal lock: with y at a quarter turn, x and z turn the same axis
+        let locked = Quaternion::from_euler(0.5, FRAC_PI_2, 0.);
+        assert_eq!(locked, Quaternion::from_euler(0.8, FRAC_PI_2, 0.3));
+    }
+
+    #[test]
+    fn slerp() {
+        let z = Vector3::new(0., 0., 1.);
+        let a = Quaternion::IDENTITY;
+        let b = Quaternion::from_axis_angle(z, FRAC_PI_2);
+        assert_eq!(a.slerp(b, 0.), a);
+        assert_eq!(a.slerp(b, 1.), b);
+        assert_eq!(a.slerp(b, 0.5), Quaternion::from_axis_angle(z, FRAC_PI_4));
+        assert_eq!(
+            a.slerp(b, 0.25),
+            Quaternion::from_axis_angle(z, FRAC_PI_2 / 4.)
+        );
+        // the shorter way round, whichever sign `b` has
+        assert_eq!(a.slerp(-b, 0.5), Quaternion::from_axis_angle(z, FRAC_PI_4));
+        assert_eq!(a.slerp(a, 0.3), a);
+    }
+
+    #[test]
+    fn decompose() {
+        let rotation = Quaternion::from_axis_angle(Vector3::new(1., -1., 2.), 2.2);
+        let m = translation(3., -2., 5.) * rotation.matrix() * scale(2., 0.5, 3.);
+        let built = Matrix4::identity()
+            .scale(2., 0.5, 3.)
+            .rotate(rotation)
+            .translate(3., -2., 5.);
+        assert_eq!(built, m);
+        let d = m.decompose().expect("affine without shear");
+        assert_eq!(d.translation, Vector3::new(3., -2., 5.));
+        assert_eq!(d.scale, Vector3::new(2., 0.5, 3.));
+        assert!(d.rotation == rotation || d.rotation == -rotation);
+        assert_eq!(d.matrix(), m);
+        // mirror images keep a proper rotation and a negative scale
+        let mirrored = rotation.matrix() * scale(-1., 1., 1.);
+        let d = mirrored.decompose().expect("affine without shear");
+        assert_eq!(d.scale, Vector3::new(-1., 1., 1.));
+        assert_eq!(d.matrix(), mirrored);
+
+        let sheared = crate::matrix::matrix::shear(1., 0., 0., 0., 0., 0.);
+        assert!(sheared.decompose().is_none());
+        assert!(scale(1., 0., 1.).decompose().is_none());
+        // tiny is not zero
+        let thin = rotation.matrix() * scale(5e-5, 1., 1.);
+        let d = thin.decompose().expect("invertible");
+        assert!(crate::equals(d.scale.x / 5e-5, 1.));
+        assert_eq!(d.matrix(), thin);
+        let mut projective = Matrix4::IDENTITY;
+        projective[3] = [0., 0., 1., 0.];
+        assert!(projective.decompose().is_none());
+    }
+}
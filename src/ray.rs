@@ -1,60 +1,132 @@
-use crate::matrix::matrix;
-use crate::tuple::Tuple;
+use crate::matrix::matrix::Matrix4;
+use crate::simd::Float4;
+use crate::tuple::{Point3, Vector3};
+use crate::Float;
 
-#[derive(Clone)]
+#[derive(Debug, Clone)]
 pub struct Ray {
-    pub origin: Tuple,
-    pub direction: Tuple,
+    pub origin: Point3,
+    pub direction: Vector3,
 }
 
-pub fn ray(origin: Tuple, direction: Tuple) -> Ray {
-    assert!(origin.is_point());
-    assert!(direction.is_vector());
+pub fn ray(origin: Point3, direction: Vector3) -> Ray {
     Ray { origin, direction }
 }
 
 impl Ray {
-    pub fn position(&self, time: f32) -> Tuple {
-        self.origin.add(self.direction.multiply(time))
+    pub fn position(&self, time: Float) -> Point3 {
+        self.origin + self.direction * time
     }
-    pub fn transform(&self, m: matrix::Matrix4) -> Ray {
+    pub fn transform(&self, m: Matrix4) -> Ray {
         Ray {
-            origin: matrix::M4::dot_tuple(m, self.origin.clone()),
-            direction: matrix::M4::dot_tuple(m, self.direction.clone()),
+            origin: m * self.origin,
+            direction: m * self.direction,
+        }
+    }
+}
+
+/// Four rays in structure-of-arrays form: lane `i` of each coordinate
+/// belongs to ray `i`. Eight rays make two packets.
+#[derive(Clone, Copy)]
+pub struct RayPacket {
+    pub origin: [Float4; 3],
+    pub direction: [Float4; 3],
+}
+
+impl RayPacket {
+    pub fn new(rays: [&Ray; 4]) -> RayPacket {
+        let lanes =
+            |f: fn(&Ray) -> Float| Float4::new(f(rays[0]), f(rays[1]), f(rays[2]), f(rays[3]));
+        RayPacket {
+            origin: [
+                lanes(|r| r.origin.x),
+                lanes(|r| r.origin.y),
+                lanes(|r| r.origin.z),
+            ],
+            direction: [
+                lanes(|r| r.direction.x),
+                lanes(|r| r.direction.y),
+                lanes(|r| r.direction.z),
+            ],
+        }
+    }
+    /// Ray `i` on its own.
+    pub fn ray(&self, i: usize) -> Ray {
+        let [ox, oy, oz] = self.origin.map(|v| v.to_array()[i]);
+        let [dx, dy, dz] = self.direction.map(|v| v.to_array()[i]);
+        ray(Point3::new(ox, oy, oz), Vector3::new(dx, dy, dz))
+    }
+    /// Every ray transformed by the affine matrix `m`, rounding as
+    /// `Ray::transform` does.
+    pub fn transform(&self, m: Matrix4) -> RayPacket {
+        let apply = |v: &[Float4; 3], w: Float| {
+            [0, 1, 2].map(|k| {
+                let r = m[k];
+                Float4::splat(r[0]) * v[0]
+                    + Float4::splat(r[1]) * v[1]
+                    + Float4::splat(r[2]) * v[2]
+                    + Float4::splat(r[3] * w)
+            })
+        };
+        RayPacket {
+            origin: apply(&self.origin, 1.),
+            direction: apply(&self.direction, 0.),
         }
     }
 }
 
 #[cfg(test)]
 mod matrix_tests {
-    use crate::matrix::matrix::{scale, translation};
-    use crate::ray::ray;
-    use crate::tuple::Tuple;
+    use crate::matrix::matrix::{rotate_y, scale, translation};
+    use crate::ray::{ray, RayPacket};
+    use crate::tuple::{Point3, Vector3};
 
     #[test]
     fn ray_test() {
-        let ray = ray(Tuple::point(2., 3., 4.), Tuple::vector(1., 0., 0.));
-        assert!(ray.position(0.).eq(&Tuple::point(2., 3., 4.)));
-        assert!(ray.position(1.).eq(&Tuple::point(3., 3., 4.)));
-        assert!(ray.position(-1.).eq(&Tuple::point(1., 3., 4.)));
-        assert!(ray.position(2.5).eq(&Tuple::point(4.5, 3., 4.)));
+        let ray = ray(Point3::new(2., 3., 4.), Vector3::new(1., 0., 0.));
+        assert!(ray.position(0.).eq(&Point3::new(2., 3., 4.)));
+        assert!(ray.position(1.).eq(&Point3::new(3., 3., 4.)));
+        assert!(ray.position(-1.).eq(&Point3::new(1., 3., 4.)));
+        assert!(ray.position(2.5).eq(&Point3::new(4.5, 3., 4.)));
     }
 
     #[test]
     fn ray_translate() {
-        let r1 = ray(Tuple::point(1., 2., 3.), Tuple::vector(0., 1., 0.));
+        let r1 = ray(Point3::new(1., 2., 3.), Vector3::new(0., 1., 0.));
         let m = translation(3., 4., 5.);
         let r2 = r1.transform(m);
-        assert!(r2.origin.eq(&Tuple::point(4., 6., 8.)));
-        assert!(r2.direction.eq(&Tuple::vector(0., 1., 0.)));
+        assert!(r2.origin.eq(&Point3::new(4., 6., 8.)));
+        assert!(r2.direction.eq(&Vector3::new(0., 1., 0.)));
     }
 
     #[test]
     fn ray_scale() {
-        let r1 = ray(Tuple::point(1., 2., 3.), Tuple::vector(0., 1., 0.));
+        let r1 = ray(Point3::new(1., 2., 3.), Vector3::new(0., 1., 0.));
         let m = scale(2., 3., 4.);
         let r2 = r1.transform(m);
-        assert!(r2.origin.eq(&Tuple::point(2., 6., 12.)));
-        assert!(r2.direction.eq(&Tuple::vector(0., 3., 0.)));
+        assert!(r2.origin.eq(&Point3::new(2., 6., 12.)));
+        assert!(r2.direction.eq(&Vector3::new(0., 3., 0.)));
+    }
+
+    #[test]
+    fn packet() {
+        let rays = [
+            ray(Point3::new(1., 2., 3.), Vector3::new(0., 1., 0.)),
+            ray(Point3::new(-4., 0.5, 0.), Vector3::new(0.6, 0., 0.8)),
+            ray(Point3::new(0., 0., -5.), Vector3::new(0., 0., 1.)),
+            ray(Point3::new(7., -1., 2.), Vector3::new(-1., -1., 0.)),
+        ];
+        let packet = RayPacket::new([&rays[0], &rays[1], &rays[2], &rays[3]]);
+        let m = translation(3., 4., 5.) * rotate_y(0.7) * scale(2., 3., 4.);
+        let moved = packet.transform(m);
+        let bits = |r: &crate::ray::Ray| {
+            let (o, d) = (r.origin, r.direction);
+            [o.x, o.y, o.z, d.x, d.y, d.z].map(|v| v.to_bits())
+        };
+        for (i, r) in rays.iter().enumerate() {
+            assert_eq!(bits(&packet.ray(i)), bits(r));
+            // lane by lane, exactly what the scalar path computes
+            assert_eq!(bits(&moved.ray(i)), bits(&r.transform(m)));
+        }
     }
 }
@@ -0,0 +1,1077 @@
+use crate::canvas::{Canvas, OutOfBounds};
+use crate::color::Color;
+use crate::light::{lighting, PointLight};
+use crate::ray::{ray, Ray, RayPacket};
+use crate::sampling::{pixel_samples, shuffle, Filter, Rng, SamplePattern};
+use crate::shapes::{hit, Shape};
+use crate::tiles::{tiles, Tile, TileOrder};
+use crate::tuple::{Normal3, Point3, Vector3};
+use crate::Float;
+use indicatif::ProgressBar;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// A pinhole looking down +z through a wall of the given size.
+pub struct Camera {
+    pub eye: Point3,
+    pub wall_z: Float,
+    pub wall_width: Float,
+    pub wall_height: Float,
+}
+
+impl Default for Camera {
+    fn default() -> Camera {
+        Camera {
+            eye: Point3::new(0., 0., -5.),
+            wall_z: 10.,
+            wall_width: 7.,
+            wall_height: 7.,
+        }
+    }
+}
+
+impl Camera {
+    /// Ray through the wall point at fractions `(u, v)` of its width and
+    /// height, measured from the top-left corner.
+    pub fn ray(&self, u: Float, v: Float) -> Ray {
+        // canvas rows run top to bottom, world y runs bottom to top
+        let world_x = self.wall_width * (u - 0.5);
+        let world_y = self.wall_height * (0.5 - v);
+        ray(
+            self.eye,
+            (Point3::new(world_x, world_y, self.wall_z) - self.eye).unit(),
+        )
+    }
+}
+
+#[derive(Clone)]
+pub struct Settings {
+    pub width: u64,
+    pub height: u64,
+    pub threads: usize,
+    pub tile_size: u64,
+    pub tile_order: TileOrder,
+    /// Camera samples per pixel, placed by `pattern` and weighted by `filter`.
+    pub samples: u32,
+    pub pattern: SamplePattern,
+    pub filter: Filter,
+    pub seed: u64,
+    /// When set, `samples` is only the starting count for each pixel.
+    pub adaptive: Option<Adaptive>,
+    /// Bounce limit for secondary rays. Shading is flat for now, so no
+    /// secondary rays are traced yet.
+    pub max_depth: u32,
+}
+
+/// Keeps adding batches of `samples` to a pixel until the standard error
+/// of its mean drops below `threshold`, or it has `max_samples`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Adaptive {
+    pub max_samples: u32,
+    pub threshold: Float,
+}
+
+impl Default for Settings {
+    fn default() -> Settings {
+        Settings {
+            width: 200,
+            height: 200,
+            threads: thread::available_parallelism().map_or(1, |n| n.get()),
+            tile_size: 16,
+            tile_order: TileOrder::Scanline,
+            samples: 1,
+            pattern: SamplePattern::Regular,
+            filter: Filter::Box,
+            seed: 0,
+            adaptive: None,
+            max_depth: 5,
+        }
+    }
+}
+
+pub struct Scene {
+    pub camera: Camera,
+    pub lights: Vec<PointLight>,
+    pub shapes: Vec<Box<dyn Shape>>,
+}
+
+pub struct Hit<'a> {
+    pub t: Float,
+    pub object: &'a dyn Shape,
+    pub position: Point3,
+    pub normal: Normal3,
+    /// Unit vector from the hit back towards the ray origin.
+    pub eye: Vector3,
+}
+
+impl Scene {
+    /// Nearest hit in front of the ray origin, if any.
+    pub fn intersect(&self, r: Ray) -> Option<Hit<'_>> {
+        let xs = self
+            .shapes
+            .iter()
+            .flat_map(|s| s.intersects(r.clone()))
+            .collect();
+        let i = hit(xs)?;
+        Some(Scene::hit_at(&r, i.t, i.object))
+    }
+    /// `intersect` for four rays at once; lane `i` is exactly what
+    /// `intersect(rays[i])` returns.
+    pub fn intersect_packet(&self, rays: [&Ray; 4]) -> [Option<Hit<'_>>; 4] {
+        let packet = RayPacket::new(rays);
+        let mut nearest: [Option<(Float, &dyn Shape)>; 4] = [None; 4];
+        for s in self.shapes.iter() {
+            for (n, t) in nearest.iter_mut().zip(s.hit_packet(&packet)) {
+                match (t, *n) {
+                    // ties go to the earlier shape, as in `hit`
+                    (Some(t), Some((best, _))) if t >= best => {}
+                    (Some(t), _) => *n = Some((t, s.as_ref())),
+                    (None, _) => {}
+                }
+            }
+        }
+        [0, 1, 2, 3].map(|i| nearest[i].map(|(t, object)| Scene::hit_at(rays[i], t, object)))
+    }
+    fn hit_at<'a>(r: &Ray, t: Float, object: &'a dyn Shape) -> Hit<'a> {
+        let position = r.position(t);
+        Hit {
+            t,
+            object,
+            normal: object.normal_at(position),
+            eye: (-r.direction).unit(),
+            position,
+        }
+    }
+    /// `intersect` for every ray, traced in packets of four with the
+    /// `simd` feature.
+    pub fn intersect_all(&self, rays: &[Ray]) -> Vec<Option<Hit<'_>>> {
+        if !cfg!(feature = "simd") {
+            return rays.iter().map(|r| self.intersect(r.clone())).collect();
+        }
+        let mut hits = Vec::with_capacity(rays.len());
+        for chunk in rays.chunks(4) {
+            // a short last chunk repeats its final ray
+            let lane = |i: usize| &chunk[i.min(chunk.len() - 1)];
+            let packet = self.intersect_packet([lane(0), lane(1), lane(2), lane(3)]);
+            hits.extend(IntoIterator::into_iter(packet).take(chunk.len()));
+        }
+        hits
+    }
+    /// Color seen along a primary ray; misses are black. Without lights
+    /// every surface shows its unlit color.
+    pub fn shade(&self, hit: &Option<Hit>) -> Color {
+        let h = match hit {
+            Some(h) => h,
+            None => return Color::default(),
+        };
+        let material = h.object.material();
+        let object_point = h.object.inverse() * h.position;
+        let surface = material.color_at(object_point);
+        if self.lights.is_empty() {
+            return surface;
+        }
+        self.lights.iter().fold(Color::default(), |c, light| {
+            c.add(lighting(
+                material,
+                &surface,
+                light,
+                &h.position,
+                &h.eye,
+                &h.normal,
+            ))
+        })
+    }
+}
+
+/// Everything the renderer writes per pixel. Scalar passes store their
+/// value in all three channels, vector passes store x/y/z in r/g/b, and
+/// pixels whose ray missed have alpha 0 in every pass.
+#[derive(Clone)]
+pub struct Frame {
+    pub beauty: Canvas,
+    pub depth: Canvas,
+    pub normal: Canvas,
+    pub position: Canvas,
+    pub object_id: Canvas,
+    pub material_id: Canvas,
+    /// Camera samples traced for each pixel; see `heatmap`.
+    pub sample_count: Canvas,
+}
+
+fn scalar(v: Float) -> Color {
+    Color { r: v, g: v, b: v }
+}
+
+fn vector(x: Float, y: Float, z: Float) -> Color {
+    Color { r: x, g: y, b: z }
+}
+
+impl Frame {
+    pub fn new(width: u64, height: u64) -> Frame {
+        Frame {
+            beauty: Canvas::new(width, height),
+            depth: Canvas::new(width, height),
+            normal: Canvas::new(width, height),
+            position: Canvas::new(width, height),
+            object_id: Canvas::new(width, height),
+            material_id: Canvas::new(width, height),
+            sample_count: Canvas::new(width, height),
+        }
+    }
+    pub(crate) fn passes(&self) -> [&Canvas; 7] {
+        [
+            &self.beauty,
+            &self.depth,
+            &self.normal,
+            &self.position,
+            &self.object_id,
+            &self.material_id,
+            &self.sample_count,
+        ]
+    }
+    pub(crate) fn passes_mut(&mut self) -> [&mut Canvas; 7] {
+        [
+            &mut self.beauty,
+            &mut self.depth,
+            &mut self.normal,
+            &mut self.position,
+            &mut self.object_id,
+            &mut self.material_id,
+            &mut self.sample_count,
+        ]
+    }
+    pub fn record(
+        &mut self,
+        x: u64,
+        y: u64,
+        hit: &Option<Hit>,
+        color: Color,
+    ) -> Result<(), OutOfBounds> {
+        let values = match hit {
+            Some(h) => [
+                color,
+                scalar(h.t),
+                vector(h.normal.x, h.normal.y, h.normal.z),
+                vector(h.position.x, h.position.y, h.position.z),
+                scalar(h.object.id() as Float),
+                scalar(h.object.material().id() as Float),
+            ],
+            None => Default::default(),
+        };
+        let coverage = if hit.is_some() { 1. } else { 0. };
+        for (pass, value) in self.passes_mut().iter_mut().zip(values.iter()) {
+            pass.set(x, y, value.clone())?;
+            pass.set_alpha(x, y, coverage)?;
+        }
+        Ok(())
+    }
+    /// Copies every pass of `src` into this frame at `(x, y)`.
+    pub fn blit(&mut self, src: &Frame, x: u64, y: u64) {
+        for (dst, src) in self.passes_mut().iter_mut().zip(src.passes().iter()) {
+            dst.blit(src, x, y);
+        }
+    }
+}
+
+/// One camera sample; `x` and `y` are in pixels from the top-left corner
+/// of the image.
+struct Sample {
+    x: Float,
+    y: Float,
+    color: Color,
+    coverage: Float,
+}
+
+/// One sample for each point, in image coordinates.
+fn trace_samples(scene: &Scene, settings: &Settings, points: &[(Float, Float)]) -> Vec<Sample> {
+    let (width, height) = (settings.width as Float, settings.height as Float);
+    let rays: Vec<Ray> = points
+        .iter()
+        .map(|&(x, y)| scene.camera.ray(x / width, y / height))
+        .collect();
+    scene
+        .intersect_all(&rays)
+        .iter()
+        .zip(points)
+        .map(|(hit, &(x, y))| Sample {
+            x,
+            y,
+            color: scene.shade(hit),
+            coverage: if hit.is_some() { 1. } else { 0. },
+        })
+        .collect()
+}
+
+fn luminance(c: &Color) -> Float {
+    0.2126 * c.r + 0.7152 * c.g + 0.0722 * c.b
+}
+
+/// Largest standard error of the mean over luminance and coverage.
+fn standard_error(samples: &[Sample]) -> Float {
+    let n = samples.len() as Float;
+    if samples.len() < 2 {
+        return Float::INFINITY;
+    }
+    let error = |values: &dyn Fn(&Sample) -> Float| {
+        let mean = samples.iter().map(values).sum::<Float>() / n;
+        let var = samples
+            .iter()
+            .map(|s| (values(s) - mean).powi(2))
+            .sum::<Float>()
+            / (n - 1.);
+        (var / n).sqrt()
+    };
+    error(&|s| luminance(&s.color)).max(error(&|s| s.coverage))
+}
+
+/// Where pixel `(x, y)` is sampled without `adaptive`, in image
+/// coordinates.
+fn pixel_points(settings: &Settings, x: u64, y: u64) -> Vec<(Float, Float)> {
+    let mut rng = Rng::for_pixel(settings.seed, x, y);
+    pixel_samples(settings.pattern, settings.samples, &mut rng)
+        .iter()
+        .map(|&(u, v)| (x as Float + u, y as Float + v))
+        .collect()
+}
+
+/// Every sample traced for pixel `(x, y)` with `adaptive` sampling.
+fn trace_adaptive(
+    scene: &Scene,
+    settings: &Settings,
+    adaptive: &Adaptive,
+    x: u64,
+    y: u64,
+) -> Vec<Sample> {
+    let mut rng = Rng::for_pixel(settings.seed, x, y);
+    let batch = settings.samples.max(1);
+    let mut points = pixel_samples(settings.pattern, adaptive.max_samples.max(batch), &mut rng);
+    // low-discrepancy sequences are well spread at any prefix; grids are not
+    if let SamplePattern::Regular | SamplePattern::Jittered = settings.pattern {
+        shuffle(&mut points, &mut rng);
+    }
+    for p in points.iter_mut() {
+        *p = (x as Float + p.0, y as Float + p.1);
+    }
+    let mut traced = Vec::with_capacity(points.len());
+    for chunk in points.chunks(batch as usize) {
+        traced.extend(trace_samples(scene, settings, chunk));
+        if standard_error(&traced) < adaptive.threshold {
+            break;
+        }
+    }
+    traced
+}
+
+/// Colors a `sample_count` pass from blue (no samples) to red (`max`).
+pub fn heatmap(counts: &Canvas, max: Float) -> Canvas {
+    let mut out = Canvas::new(counts.width, counts.height);
+    for ((_, _, p), (_, _, c)) in out.pixels_mut().zip(counts.pixels()) {
+        let t = (c.r / max.max(1.)).clamp(0., 1.);
+        *p = Color {
+            r: t,
+            g: 0.,
+            b: 1. - t,
+        };
+    }
+    out
+}
+
+fn render_tile(scene: &Scene, settings: &Settings, tile: &Tile) -> Frame {
+    let (width, height) = (settings.width, settings.height);
+    let mut out = Frame::new(tile.width, tile.height);
+    // AOVs come from a single ray through each pixel center
+    let pixels: Vec<(u64, u64)> = (0..tile.height)
+        .flat_map(|dy| (0..tile.width).map(move |dx| (dx, dy)))
+        .collect();
+    let rays: Vec<Ray> = pixels
+        .iter()
+        .map(|&(dx, dy)| {
+            let (x, y) = ((tile.x + dx) as Float + 0.5, (tile.y + dy) as Float + 0.5);
+            scene.camera.ray(x / width as Float, y / height as Float)
+        })
+        .collect();
+    for (&(dx, dy), hit) in pixels.iter().zip(scene.intersect_all(&rays)) {
+        out.record(dx, dy, &hit, scene.shade(&hit))
+            .expect("pixel in bounds");
+    }
+
+    // Samples from pixels around the tile also land inside the filter
+    // support, so trace an apron of `reach` pixels on every side. Doing
+    // this per tile (rather than splatting into shared memory) keeps the
+    // result independent of tiling and thread scheduling.
+    let reach = settings.filter.reach();
+    let (x0, y0) = (tile.x.saturating_sub(reach), tile.y.saturating_sub(reach));
+    let x1 = (tile.x + tile.width + reach).min(width);
+    let y1 = (tile.y + tile.height + reach).min(height);
+    let apron = (y0..y1).flat_map(|y| (x0..x1).map(move |x| (x, y)));
+    let samples: Vec<Vec<Sample>> = match &settings.adaptive {
+        Some(adaptive) => apron
+            .map(|(x, y)| trace_adaptive(scene, settings, adaptive, x, y))
+            .collect(),
+        None => {
+            // traced together so packets stay full at one sample per pixel
+            let points: Vec<Vec<(Float, Float)>> =
+                apron.map(|(x, y)| pixel_points(settings, x, y)).collect();
+            let mut traced = trace_samples(scene, settings, &points.concat()).into_iter();
+            points
+                .iter()
+                .map(|p| traced.by_ref().take(p.len()).collect())
+                .collect()
+        }
+    };
+
+    for dy in 0..tile.height {
+        for dx in 0..tile.width {
+            let (x, y) = (tile.x + dx, tile.y + dy);
+            let (cx, cy) = (x as Float + 0.5, y as Float + 0.5);
+            let mut color = Color::default();
+            let mut coverage = 0.;
+            let mut total = 0.;
+            for ny in y.saturating_sub(reach)..(y + reach + 1).min(height) {
+                for nx in x.saturating_sub(reach)..(x + reach + 1).min(width) {
+                    let i = ((ny - y0) * (x1 - x0) + nx - x0) as usize;
+                    for sample in samples[i].iter() {
+                        let w = settings.filter.weight(sample.x - cx, sample.y - cy);
+                        color = color.add(sample.color.multiply(w));
+                        coverage += sample.coverage * w;
+                        total += w;
+                    }
+                }
+            }
+            if total != 0. {
+                color = color.multiply(1. / total);
+                coverage /= total;
+            }
+            let own = samples[((y - y0) * (x1 - x0) + x - x0) as usize].len();
+            out.sample_count
+                .set(dx, dy, scalar(own as Float))
+                .expect("pixel in bounds");
+            out.beauty.set(dx, dy, color).expect("pixel in bounds");
+            out.beauty
+                .set_alpha(dx, dy, coverage.clamp(0., 1.))
+                .expect("pixel in bounds");
+        }
+    }
+    out
+}
+
+/// Renders `tiles` on `settings.threads` workers, handing them out in list
+/// order. Each finished tile is passed to `on_tile` along with its pixels,
+/// on the calling thread. Every pixel is computed independently, so the
+/// result does not depend on the thread count.
+pub fn render_tiles<F>(scene: &Scene, settings: &Settings, tiles: &[Tile], mut on_tile: F)
+where
+    F: FnMut(&Tile, Frame),
+{
+    let next_tile = AtomicUsize::new(0);
+    let (tx, rx) = mpsc::channel();
+    thread::scope(|s| {
+        for _ in 0..settings.threads.max(1) {
+            let tx = tx.clone();
+            let next_tile = &next_tile;
+            s.spawn(move || loop {
+                let i = next_tile.fetch_add(1, Ordering::Relaxed);
+                let tile = match tiles.get(i) {
+                    Some(t) => t,
+                    None => break,
+                };
+                tx.send((tile, render_tile(scene, settings, tile)))
+                    .expect("render result dropped");
+            });
+        }
+        drop(tx);
+        for (tile, pixels) in rx {
+            on_tile(tile, pixels);
+        }
+    });
+}
+
+/// Renders the whole image, ticking `bar` once per finished tile.
+pub fn render(scene: &Scene, settings: &Settings, bar: &ProgressBar) -> Frame {
+    let mut frame = Frame::new(settings.width, settings.height);
+    let tiles = tiles(
+        settings.width,
+        settings.height,
+        settings.tile_size,
+        settings.tile_order,
+    );
+    bar.set_length(tiles.len() as u64);
+    render_tiles(scene, settings, &tiles, |tile, pixels| {
+        frame.blit(&pixels, tile.x, tile.y);
+        bar.inc(1);
+    });
+    frame
+}
+
+/// Running sum of progressive passes. Each pass traces one jittered sample
+/// per pixel (whatever `settings.pattern` says) with its own seed, and the
+/// averaged image refines as passes are added.
+pub struct Accumulator {
+    pub passes: u32,
+    pub(crate) sum: Canvas,
+    pub(crate) frame: Frame,
+}
+
+impl Accumulator {
+    pub fn new(width: u64, height: u64) -> Accumulator {
+        let mut sum = Canvas::new(width, height);
+        for (x, y) in (0..height).flat_map(|y| (0..width).map(move |x| (x, y))) {
+            sum.set_alpha(x, y, 0.).expect("pixel in bounds");
+        }
+        Accumulator {
+            passes: 0,
+            sum,
+            frame: Frame::new(width, height),
+        }
+    }
+    /// `settings` for pass number `pass`. Every pass traces one jittered
+    /// sample per pixel, so `samples`, `pattern` and `adaptive` are
+    /// replaced, and `seed` is derived from the base seed and `pass`.
+    pub fn pass_settings(settings: &Settings, pass: u32) -> Settings {
+        Settings {
+            samples: 1,
+            pattern: SamplePattern::Jittered,
+            seed: Rng::new(settings.seed ^ pass as u64).next_u64(),
+            adaptive: None,
+            ..settings.clone()
+        }
+    }
+    pub fn add_pass(&mut self, scene: &Scene, settings: &Settings) {
+        let pass_settings = Accumulator::pass_settings(settings, self.passes);
+        let pass = render(scene, &pass_settings, &ProgressBar::hidden());
+        self.add(&pass);
+    }
+    pub fn add(&mut self, pass: &Frame) {
+        for (x, y, c) in pass.beauty.pixels() {
+            let sum = self.sum.get_mut(x, y).expect("pixel in bounds");
+            *sum = sum.add(c.clone());
+            let coverage = self.sum.get_alpha(x, y).expect("pixel in bounds");
+            self.sum
+                .set_alpha(
+                    x,
+                    y,
+                    coverage + pass.beauty.get_alpha(x, y).expect("pixel in bounds"),
+                )
+                .expect("pixel in bounds");
+        }
+        if self.passes == 0 {
+            // AOVs come from pixel-center rays, so every pass has the same ones
+            self.frame = pass.clone();
+        }
+        self.passes += 1;
+    }
+    /// The current average, with `sample_count` summed over passes.
+    pub fn frame(&self) -> Frame {
+        let mut frame = self.frame.clone();
+        let k = 1. / self.passes.max(1) as Float;
+        for (x, y, c) in self.sum.pixels() {
+            let a = self.sum.get_alpha(x, y).expect("pixel in bounds");
+            frame
+                .beauty
+                .set(x, y, c.multiply(k))
+                .expect("pixel in bounds");
+            frame
+                .beauty
+                .set_alpha(x, y, a * k)
+                .expect("pixel in bounds");
+            let count = self.frame.sample_count.get(x, y).expect("pixel in bounds");
+            frame
+                .sample_count
+                .set(x, y, count.multiply(self.passes as Float))
+                .expect("pixel in bounds");
+        }
+        frame
+    }
+}
+
+/// When progressive rendering hands out snapshots.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Snapshots {
+    EveryPasses(u32),
+    Every(Duration),
+}
+
+/// Runs `passes` progressive passes, ticking `bar` once per pass. `on_snapshot`
+/// gets the pass count and averaged frame whenever `snapshots` says one is
+/// due, and always after the last pass.
+pub fn render_progressive<F>(
+    scene: &Scene,
+    settings: &Settings,
+    passes: u32,
+    snapshots: &Snapshots,
+    bar: &ProgressBar,
+    mut on_snapshot: F,
+) -> Frame
+where
+    F: FnMut(u32, &Frame),
+{
+    let mut acc = Accumulator::new(settings.width, settings.height);
+    bar.set_length(passes as u64);
+    let mut last = Instant::now();
+    while acc.passes < passes {
+        acc.add_pass(scene, settings);
+        bar.inc(1);
+        let due = match snapshots {
+            Snapshots::EveryPasses(n) => acc.passes % (*n).max(1) == 0,
+            Snapshots::Every(interval) => last.elapsed() >= *interval,
+        };
+        if due || acc.passes == passes {
+            last = Instant::now();
+            on_snapshot(acc.passes, &acc.frame());
+        }
+    }
+    acc.frame()
+}
+
+#[cfg(test)]
+mod render_tests {
+    use super::*;
+    use crate::compare::compare;
+    use crate::light::point_light;
+    use crate::material::material;
+    use crate::matrix::matrix::translation;
+    use crate::shapes;
+
+    fn scene() -> Scene {
+        let red = material(Color {
+            r: 1.,
+            g: 0.,
+            b: 0.,
+        });
+        Scene {
+            camera: Camera::default(),
+            lights: vec![],
+            shapes: vec![Box::new(shapes::sphere_unit().with_material(red))],
+        }
+    }
+
+    #[test]
+    fn camera_ray() {
+        let c = Camera::default();
+        let r = c.ray(0.5, 0.5);
+        assert!(r.origin.eq(&Point3::new(0., 0., -5.)));
+        assert!(r.direction.eq(&Vector3::new(0., 0., 1.)));
+        let top_left = c.ray(0., 0.);
+        assert!(top_left.direction.x < 0.);
+        assert!(top_left.direction.y > 0.);
+    }
+
+    #[test]
+    fn intersect() {
+        let s = scene();
+        let h = s
+            .intersect(s.camera.ray(0.5, 0.5))
+            .expect("should hit sphere");
+        assert!(crate::equals(h.t, 4.));
+        assert!(h.position.eq(&Point3::new(0., 0., -1.)));
+        assert_eq!(h.normal, Normal3::new(0., 0., -1.));
+        assert!(s.intersect(s.camera.ray(0., 0.)).is_none());
+
+        let behind = Scene {
+            camera: Camera::default(),
+            lights: vec![],
+            shapes: vec![Box::new(shapes::sphere(translation(0., 0., -10.)))],
+        };
+        assert!(behind.intersect(behind.camera.ray(0.5, 0.5)).is_none());
+    }
+
+    #[test]
+    fn packets() {
+        // the first two spheres coincide, so the first must win the tie
+        let s = Scene {
+            camera: Camera::default(),
+            lights: vec![],
+            shapes: vec![
+                Box::new(shapes::sphere(translation(0.5, 0., 0.))),
+                Box::new(shapes::sphere(translation(0.5, 0., 0.))),
+                Box::new(shapes::sphere(translation(-1., 0.3, 2.))),
+            ],
+        };
+        let mut rays: Vec<Ray> = [(0.5, 0.5), (0., 0.), (0.3, 0.45), (0.62, 0.5), (0.4, 0.6)]
+            .iter()
+            .map(|&(x, y)| s.camera.ray(x, y))
+            .collect();
+        // from inside the first sphere
+        rays.push(ray(Point3::new(0.5, 0., 0.), Vector3::new(0.2, 1., 0.)));
+
+        let one_by_one: Vec<Option<Hit>> = rays.iter().map(|r| s.intersect(r.clone())).collect();
+        let packet = s.intersect_packet([&rays[0], &rays[1], &rays[2], &rays[3]]);
+        let all = s.intersect_all(&rays);
+        assert_eq!(all.len(), rays.len());
+        let hits = one_by_one
+            .iter()
+            .zip(packet.iter().chain(all.iter().skip(4)));
+        for (expected, h) in hits.chain(one_by_one.iter().zip(all.iter())) {
+            match (expected, h) {
+                (Some(a), Some(b)) => {
+                    assert_eq!(a.t.to_bits(), b.t.to_bits());
+                    assert_eq!(a.object.id(), b.object.id());
+                    assert_eq!(a.normal, b.normal);
+                }
+                (None, None) => {}
+                _ => panic!("packet and scalar hits disagree"),
+            }
+        }
+        assert_eq!(
+            packet[0].as_ref().expect("hits").object.id(),
+            s.shapes[0].id()
+        );
+        assert!(packet[1].is_none());
+    }
+
+    #[test]
+    fn lit() {
+        let mut s = scene();
+        let center = s.camera.ray(0.5, 0.5);
+        let flat = s.shade(&s.intersect(center.clone()));
+        assert!(
+            flat == Color {
+                r: 1.,
+                g: 0.,
+                b: 0.
+            }
+        );
+        let white = Color {
+            r: 1.,
+            g: 1.,
+            b: 1.,
+        };
+        s.lights.push(point_light(Point3::new(0., 0., -10.), white));
+        // ambient + diffuse in the material color, specular in the light's
+        let lit = s.shade(&s.intersect(center));
+        assert!(
+            lit == Color {
+                r: 1.9,
+                g: 0.9,
+                b: 0.9
+            }
+        );
+        assert!(s.shade(&None) == Color::default());
+    }
+
+    #[test]
+    fn aovs() {
+        let s = scene();
+        let id = s.shapes[0].id() as Float;
+        let material_id = s.shapes[0].material().id() as Float;
+        let settings = Settings {
+            width: 5,
+            height: 5,
+            threads: 1,
+            ..Settings::default()
+        };
+        let frame = render(&s, &settings, &ProgressBar::hidden());
+        assert!(
+            frame.beauty.get(2, 2)
+                == Some(Color {
+                    r: 1.,
+                    g: 0.,
+                    b: 0.
+                })
+        );
+        assert!(frame.object_id.get(2, 2) == Some(scalar(id)));
+        assert!(frame.material_id.get(2, 2) == Some(scalar(material_id)));
+        assert!(frame.depth.get(2, 2) == Some(scalar(4.)));
+        assert!(frame.normal.get(2, 2) == Some(vector(0., 0., -1.)));
+        assert_eq!(frame.position.get_alpha(2, 2), Some(1.));
+        for pass in [&frame.beauty, &frame.depth, &frame.object_id].iter() {
+            assert_eq!(pass.get_alpha(0, 0), Some(0.));
+            assert!(pass.get(0, 0) == Some(Color::default()));
+        }
+    }
+
+    #[test]
+    fn threads_match_single_threaded() {
+        let mut s = scene();
+        s.shapes
+            .push(Box::new(shapes::sphere(translation(1., 1., -1.))));
+        let mut settings = Settings {
+            width: 16,
+            height: 12,
+            threads: 1,
+            tile_size: 8,
+            tile_order: TileOrder::Scanline,
+            samples: 2,
+            pattern: SamplePattern::Jittered,
+            filter: Filter::Mitchell,
+            seed: 3,
+            adaptive: None,
+            max_depth: 5,
+        };
+        let bar = ProgressBar::hidden();
+        let single = render(&s, &settings, &bar);
+        assert_eq!(bar.position(), 4);
+        for &order in [TileOrder::Scanline, TileOrder::Spiral, TileOrder::Hilbert].iter() {
+            settings.threads = 8;
+            settings.tile_size = 5;
+            settings.tile_order = order;
+            let multi = render(&s, &settings, &bar);
+            for (a, b) in single.passes().iter().zip(multi.passes().iter()) {
+                let c = compare(a, b, 0.).expect("same size");
+                assert_eq!(c.max_error, 0.);
+                for (x, y, _) in a.pixels() {
+                    assert_eq!(a.get_alpha(x, y), b.get_alpha(x, y));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn tile_progress() {
+        let s = scene();
+        let settings = Settings {
+            width: 20,
+            height: 10,
+            threads: 3,
+            tile_size: 4,
+            tile_order: TileOrder::Spiral,
+            ..Settings::default()
+        };
+        let ts = tiles(20, 10, 4, TileOrder::Spiral);
+        let mut frame = Frame::new(20, 10);
+        let mut done = vec![];
+        render_tiles(&s, &settings, &ts, |tile, pixels| {
+            assert_eq!(
+                (pixels.beauty.width, pixels.beauty.height),
+                (tile.width, tile.height)
+            );
+            frame.blit(&pixels, tile.x, tile.y);
+            done.push(tile.index);
+        });
+        done.sort();
+        assert_eq!(done, (0..ts.len()).collect::<Vec<_>>());
+        assert_eq!(frame.beauty.get_alpha(10, 5), Some(1.));
+    }
+
+    #[test]
+    fn pixel_centers() {
+        // a sphere whose silhouette edge falls exactly between two pixels
+        let s = scene();
+        let settings = Settings {
+            width: 7,
+            height: 7,
+            threads: 1,
+            ..Settings::default()
+        };
+        let frame = render(&s, &settings, &ProgressBar::hidden());
+        let row: Vec<Float> = (0..7)
+            .map(|x| frame.beauty.get_alpha(x, 3).expect("in bounds"))
+            .collect();
+        // symmetric about the middle pixel now that rays go through centers
+        for x in 0..7 {
+            assert_eq!(row[x], row[6 - x]);
+        }
+        assert_eq!(row[3], 1.);
+    }
+
+    #[test]
+    fn antialiasing() {
+        let s = scene();
+        let mut settings = Settings {
+            width: 8,
+            height: 8,
+            threads: 2,
+            samples: 4,
+            ..Settings::default()
+        };
+        let bar = ProgressBar::hidden();
+        let mut filtered = vec![];
+        for &pattern in [
+            SamplePattern::Regular,
+            SamplePattern::Jittered,
+            SamplePattern::Halton,
+            SamplePattern::Sobol,
+        ]
+        .iter()
+        {
+            for &filter in [
+                Filter::Box,
+                Filter::Tent,
+                Filter::Gaussian,
+                Filter::Mitchell,
+            ]
+            .iter()
+            {
+                settings.pattern = pattern;
+                settings.filter = filter;
+                let frame = render(&s, &settings, &bar);
+                let partial = frame
+                    .beauty
+                    .pixels()
+                    .filter(|&(x, y, _)| {
+                        let a = frame.beauty.get_alpha(x, y).expect("in bounds");
+                        a > 0.05 && a < 0.95
+                    })
+                    .count();
+                assert!(partial > 0, "{:?} {:?}", pattern, filter);
+                // AOVs stay unfiltered
+                let ids = &frame.object_id;
+                assert!(ids.pixels().all(|(x, y, _)| {
+                    let a = ids.get_alpha(x, y).expect("in bounds");
+                    a == 0. || a == 1.
+                }));
+                filtered.push(frame.beauty);
+            }
+        }
+        // premultiplied: red equals coverage for a pure red sphere on black
+        let box_regular = &filtered[0];
+        for (x, y, c) in box_regular.pixels() {
+            assert!(crate::equals(
+                c.r,
+                box_regular.get_alpha(x, y).expect("in bounds")
+            ));
+        }
+    }
+
+    #[test]
+    fn adaptive_sampling() {
+        let s = scene();
+        let mut settings = Settings {
+            width: 12,
+            height: 12,
+            threads: 2,
+            samples: 4,
+            pattern: SamplePattern::Halton,
+            ..Settings::default()
+        };
+        let bar = ProgressBar::hidden();
+        let uniform = render(&s, &settings, &bar);
+        assert!(uniform.sample_count.pixels().all(|(_, _, c)| c.r == 4.));
+
+        settings.adaptive = Some(Adaptive {
+            max_samples: 32,
+            threshold: 0.01,
+        });
+        let frame = render(&s, &settings, &bar);
+        let count = |x, y| frame.sample_count.get(x, y).expect("in bounds").r;
+        // flat background and sphere interior stop after the first batch
+        assert_eq!(count(0, 0), 4.);
+        assert_eq!(count(6, 6), 4.);
+        // silhouette pixels keep going
+        let max = frame
+            .sample_count
+            .pixels()
+            .map(|(_, _, c)| c.r)
+            .fold(0., Float::max);
+        assert_eq!(max, 32.);
+        let edge = frame
+            .beauty
+            .pixels()
+            .find(|&(x, y, _)| count(x, y) == 32.)
+            .expect("some pixel refined");
+        let a = frame.beauty.get_alpha(edge.0, edge.1).expect("in bounds");
+        assert!(a > 0. && a < 1.);
+
+        let heat = heatmap(&frame.sample_count, 32.);
+        assert!(heat.get(0, 0).expect("in bounds").b > 0.8);
+        assert!(
+            heat.get(edge.0, edge.1)
+                == Some(Color {
+                    r: 1.,
+                    g: 0.,
+                    b: 0.
+                })
+        );
+    }
+
+    #[test]
+    fn progressive() {
+        let s = scene();
+        let settings = Settings {
+            width: 8,
+            height: 8,
+            threads: 2,
+            ..Settings::default()
+        };
+        let bar = ProgressBar::hidden();
+        let mut snapshots = vec![];
+        let frame = render_progressive(
+            &s,
+            &settings,
+            5,
+            &Snapshots::EveryPasses(2),
+            &bar,
+            |pass, frame| snapshots.push((pass, frame.beauty.clone())),
+        );
+        let passes: Vec<u32> = snapshots.iter().map(|(p, _)| *p).collect();
+        assert_eq!(passes, vec![2, 4, 5]);
+        assert_eq!(bar.position(), 5);
+        assert!(frame.sample_count.get(0, 0) == Some(scalar(5.)));
+        // coverage of each pixel is the mean of five 0/1 samples
+        for (x, y, c) in frame.beauty.pixels() {
+            let a = frame.beauty.get_alpha(x, y).expect("in bounds");
+            assert!(crate::equals((a * 5.).round(), a * 5.));
+            assert!(crate::equals(c.r, a));
+        }
+        // solid interior and background are exact after any number of passes
+        assert_eq!(frame.beauty.get_alpha(4, 4), Some(1.));
+        assert_eq!(frame.beauty.get_alpha(0, 0), Some(0.));
+
+        let mut acc = Accumulator::new(8, 8);
+        for _ in 0..5 {
+            acc.add_pass(&s, &settings);
+        }
+        assert_eq!(
+            compare(&acc.frame().beauty, &frame.beauty, 0.)
+                .expect("same size")
+                .max_error,
+            0.
+        );
+    }
+
+    #[test]
+    fn progressive_passes_are_jittered() {
+        let s = scene();
+        let settings = Settings {
+            width: 8,
+            height: 8,
+            threads: 1,
+            samples: 4,
+            pattern: SamplePattern::Sobol,
+            seed: 5,
+            adaptive: Some(Adaptive {
+                max_samples: 16,
+                threshold: 0.001,
+            }),
+            ..Settings::default()
+        };
+        let bar = ProgressBar::hidden();
+        let frame = render_progressive(
+            &s,
+            &settings,
+            3,
+            &Snapshots::EveryPasses(3),
+            &bar,
+            |_, _| {},
+        );
+
+        let mut sum = Canvas::new(8, 8);
+        for pass in 0..3 {
+            let jittered = Settings {
+                samples: 1,
+                pattern: SamplePattern::Jittered,
+                seed: Rng::new(5 ^ pass).next_u64(),
+                adaptive: None,
+                ..settings.clone()
+            };
+            let beauty = render(&s, &jittered, &bar).beauty;
+            for (x, y, c) in beauty.pixels() {
+                let total = sum.get_mut(x, y).expect("in bounds");
+                *total = total.add(c.clone());
+            }
+        }
+        for (x, y, c) in frame.beauty.pixels() {
+            let expected = sum.get(x, y).expect("in bounds").multiply(1. / 3.);
+            assert!(*c == expected);
+        }
+        let configured = render(&s, &settings, &bar).beauty;
+        assert!(
+            compare(&configured, &frame.beauty, 0.)
+                .expect("same size")
+                .max_error
+                > 0.
+        );
+    }
+}
@@ -0,0 +1,285 @@
+use crate::Float;
+
+/// Where the samples inside a pixel go. All patterns return points in
+/// `[0, 1)^2`; a single `Regular` sample sits at the pixel center.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SamplePattern {
+    Regular,
+    /// One random point inside each cell of the regular grid.
+    Jittered,
+    Halton,
+    Sobol,
+}
+
+/// How samples are weighted when reconstructing a pixel, as a function of
+/// their offset from the pixel center.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Filter {
+    Box,
+    Tent,
+    Gaussian,
+    /// Mitchell-Netravali with B = C = 1/3.
+    Mitchell,
+}
+
+/// SplitMix64; small, fast, and its whole state is one `u64`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Rng {
+    pub state: u64,
+}
+
+impl Rng {
+    pub fn new(seed: u64) -> Rng {
+        Rng { state: seed }
+    }
+    /// Independent stream for one pixel, so results do not depend on the
+    /// order pixels are rendered in.
+    pub fn for_pixel(seed: u64, x: u64, y: u64) -> Rng {
+        let mut r = Rng::new(seed ^ x.wrapping_mul(0x9e37_79b9_7f4a_7c15));
+        r.state ^= y.wrapping_mul(0xc2b2_ae3d_27d4_eb4f);
+        r.next_u64();
+        r
+    }
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+    /// Uniform in `[0, 1)`.
+    pub fn next_float(&mut self) -> Float {
+        (self.next_u64() >> 40) as Float / (1u64 << 24) as Float
+    }
+}
+
+/// Fisher-Yates shuffle.
+pub fn shuffle<T>(v: &mut [T], rng: &mut Rng) {
+    for i in (1..v.len()).rev() {
+        let j = (rng.next_u64() % (i as u64 + 1)) as usize;
+        v.swap(i, j);
+    }
+}
+
+fn radical_inverse(base: u32, mut i: u32) -> Float {
+    let inv = 1. / base as f64;
+    let mut f = inv;
+    let mut r = 0.;
+    while i > 0 {
+        r += f * (i % base) as f64;
+        i /= base;
+        f *= inv;
+    }
+    r as Float
+}
+
+fn sobol_2(i: u32) -> Float {
+    // second Sobol dimension: v_0 = 1/2, v_k = v_{k-1} ^ (v_{k-1} >> 1)
+    let mut v = 1u32 << 31;
+    let mut r = 0u32;
+    let mut i = i;
+    while i > 0 {
+        if i & 1 == 1 {
+            r ^= v;
+        }
+        i >>= 1;
+        v ^= v >> 1;
+    }
+    r as Float / (1u64 << 32) as Float
+}
+
+fn wrap(f: Float) -> Float {
+    let f = f - f.floor();
+    if f >= 1. {
+        0.
+    } else {
+        f
+    }
+}
+
+/// `n` sample offsets inside one pixel. The low-discrepancy patterns are
+/// randomly shifted (Cranley-Patterson rotation) per pixel by `rng`.
+pub fn pixel_samples(pattern: SamplePattern, n: u32, rng: &mut Rng) -> Vec<(Float, Float)> {
+    let n = n.max(1);
+    let cols = (n as Float).sqrt().ceil() as u32;
+    let rows = n.div_ceil(cols);
+    let grid = |i: u32, jx: Float, jy: Float| {
+        (
+            ((i % cols) as Float + jx) / cols as Float,
+            ((i / cols) as Float + jy) / rows as Float,
+        )
+    };
+    match pattern {
+        SamplePattern::Regular => (0..n).map(|i| grid(i, 0.5, 0.5)).collect(),
+        SamplePattern::Jittered => (0..n)
+            .map(|i| {
+                let jx = rng.next_float();
+                grid(i, jx, rng.next_float())
+            })
+            .collect(),
+        SamplePattern::Halton | SamplePattern::Sobol => {
+            let (sx, sy) = (rng.next_float(), rng.next_float());
+            (0..n)
+                .map(|i| {
+                    let (u, v) = match pattern {
+                        SamplePattern::Halton => (radical_inverse(2, i), radical_inverse(3, i)),
+                        _ => (radical_inverse(2, i), sobol_2(i)),
+                    };
+                    (wrap(u + sx), wrap(v + sy))
+                })
+                .collect()
+        }
+    }
+}
+
+fn mitchell_1d(x: Float) -> Float {
+    let (b, c) = (1. / 3., 1. / 3.);
+    let x = x.abs();
+    if x < 1. {
+        ((12. - 9. * b - 6. * c) * x * x * x + (-18. + 12. * b + 6. * c) * x * x + (6. - 2. * b))
+            / 6.
+    } else if x < 2. {
+        ((-b - 6. * c) * x * x * x
+            + (6. * b + 30. * c) * x * x
+            + (-12. * b - 48. * c) * x
+            + (8. * b + 24. * c))
+            / 6.
+    } else {
+        0.
+    }
+}
+
+impl Filter {
+    /// Half-width of the filter's support, in pixels.
+    pub fn radius(&self) -> Float {
+        match self {
+            Filter::Box => 0.5,
+            Filter::Tent => 1.,
+            Filter::Gaussian => 1.5,
+            Filter::Mitchell => 2.,
+        }
+    }
+    /// How many neighbouring pixels on each side can have samples inside
+    /// the support.
+    pub fn reach(&self) -> u64 {
+        ((self.radius() + 0.5).ceil() - 1.) as u64
+    }
+    fn weight_1d(&self, d: Float) -> Float {
+        let r = self.radius();
+        match self {
+            // half-open, so a sample on a pixel edge counts exactly once
+            Filter::Box => (-r <= d && d < r) as u8 as Float,
+            Filter::Tent => (1. - d.abs() / r).max(0.),
+            Filter::Gaussian => {
+                let alpha = 2.;
+                ((-alpha * d * d).exp() - (-alpha * r * r).exp()).max(0.)
+            }
+            Filter::Mitchell => mitchell_1d(d * 2. / r),
+        }
+    }
+    /// Weight of a sample `(dx, dy)` pixels away from the pixel center.
+    pub fn weight(&self, dx: Float, dy: Float) -> Float {
+        self.weight_1d(dx) * self.weight_1d(dy)
+    }
+}
+
+#[cfg(test)]
+mod sampling_tests {
+    use super::*;
+    use crate::equals;
+
+    fn in_unit_square(ps: &[(Float, Float)]) -> bool {
+        ps.iter()
+            .all(|&(u, v)| (0. ..1.).contains(&u) && (0. ..1.).contains(&v))
+    }
+
+    #[test]
+    fn rng() {
+        let mut a = Rng::for_pixel(7, 3, 4);
+        let mut b = Rng::for_pixel(7, 3, 4);
+        let mut c = Rng::for_pixel(7, 4, 3);
+        let xs: Vec<Float> = (0..100).map(|_| a.next_float()).collect();
+        assert!(xs.iter().all(|x| (0. ..1.).contains(x)));
+        assert_eq!(xs[0], b.next_float());
+        assert_ne!(xs[0], c.next_float());
+    }
+
+    #[test]
+    fn shuffled() {
+        let mut rng = Rng::new(5);
+        let mut v: Vec<u32> = (0..20).collect();
+        shuffle(&mut v, &mut rng);
+        assert_ne!(v, (0..20).collect::<Vec<u32>>());
+        v.sort();
+        assert_eq!(v, (0..20).collect::<Vec<u32>>());
+    }
+
+    #[test]
+    fn regular() {
+        let mut rng = Rng::new(0);
+        assert_eq!(
+            pixel_samples(SamplePattern::Regular, 1, &mut rng),
+            vec![(0.5, 0.5)]
+        );
+        assert_eq!(
+            pixel_samples(SamplePattern::Regular, 4, &mut rng),
+            vec![(0.25, 0.25), (0.75, 0.25), (0.25, 0.75), (0.75, 0.75)]
+        );
+        assert_eq!(pixel_samples(SamplePattern::Regular, 5, &mut rng).len(), 5);
+    }
+
+    #[test]
+    fn jittered() {
+        let mut rng = Rng::new(1);
+        let ps = pixel_samples(SamplePattern::Jittered, 9, &mut rng);
+        assert!(in_unit_square(&ps));
+        for (i, &(u, v)) in ps.iter().enumerate() {
+            assert_eq!((u * 3.) as usize, i % 3);
+            assert_eq!((v * 3.) as usize, i / 3);
+        }
+    }
+
+    #[test]
+    fn low_discrepancy() {
+        assert!(equals(radical_inverse(2, 3), 0.75));
+        assert!(equals(radical_inverse(3, 5), 7. / 9.));
+        let sobol: Vec<Float> = (0..4).map(sobol_2).collect();
+        assert_eq!(sobol, vec![0., 0.5, 0.75, 0.25]);
+        let mut rng = Rng::new(2);
+        for &p in [SamplePattern::Halton, SamplePattern::Sobol].iter() {
+            let ps = pixel_samples(p, 16, &mut rng);
+            assert_eq!(ps.len(), 16);
+            assert!(in_unit_square(&ps));
+            // one sample per column of a 16-wide stratification
+            let mut cols: Vec<usize> = ps.iter().map(|&(u, _)| (u * 16.) as usize).collect();
+            cols.sort();
+            cols.dedup();
+            assert_eq!(cols.len(), 16);
+        }
+    }
+
+    #[test]
+    fn filters() {
+        assert_eq!(Filter::Box.reach(), 0);
+        assert_eq!(Filter::Tent.reach(), 1);
+        assert_eq!(Filter::Gaussian.reach(), 1);
+        assert_eq!(Filter::Mitchell.reach(), 2);
+        assert_eq!(Filter::Box.weight(-0.5, 0.), 1.);
+        assert_eq!(Filter::Box.weight(0.5, 0.), 0.);
+        assert!(equals(Filter::Tent.weight(0.5, 0.), 0.5));
+        for &f in [
+            Filter::Box,
+            Filter::Tent,
+            Filter::Gaussian,
+            Filter::Mitchell,
+        ]
+        .iter()
+        {
+            assert!(f.weight(0., 0.) > 0.);
+            assert_eq!(f.weight(f.radius() + 0.01, 0.), 0.);
+            assert!(f.weight(0.1, 0.) >= f.weight(0.2, 0.3));
+        }
+        assert!(Filter::Mitchell.weight(1.5, 0.) < 0.);
+        assert!(equals(mitchell_1d(0.), 16. / 18.));
+    }
+}
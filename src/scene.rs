@@ -0,0 +1,1116 @@
+//! Scene files: a YAML list of `add` and `define` items.
+//!
+//! ```yaml
+//! - define: glossy
+//!   value:
+//!     specular: 1
+//!     shininess: 300
+//! - define: red-glossy
+//!   extend: glossy
+//!   value:
+//!     color: [1, 0, 0]
+//! - add: camera
+//!   eye: [0, 0, -5]
+//! - add: light
+//!   at: [-10, 10, -10]
+//!   intensity: [1, 1, 1]
+//! - add: sphere
+//!   material: red-glossy
+//!   transform:
+//!     - [scale, 1, 0.5, 1]
+//!     - [translate, 0, 1, 0]
+//! ```
+//!
+//! Transform steps apply in the order listed, like `chain`. A name
+//! defined as a list of steps can itself be used as a step, and
+//! `[matrix, ...]` takes all 16 entries of a `Matrix4`, row by row.
+//! `[rotate, x, y, z, angle]` turns about an axis, and
+//! `[reflect, px, py, pz, nx, ny, nz]` mirrors across the plane through a
+//! point with a normal.
+//!
+//! A definition whose value is a number is a variable. Anywhere a number
+//! is expected, an expression over variables and `pi` may be used
+//! instead; see `expr`.
+//!
+//! ```yaml
+//! - include: materials.yml
+//! - define: spacing
+//!   value: 2.5
+//! - add: sphere
+//!   transform:
+//!     - [rotate-y, pi / 4]
+//!     - [translate, spacing * 2, 0, 0]
+//! ```
+//!
+//! Included files are read relative to the file including them and share
+//! its definitions, as if their text had been pasted in.
+
+use crate::color::Color;
+use crate::expr;
+use crate::json;
+use crate::light::point_light;
+use crate::material::{material, Material};
+use crate::matrix::matrix::Matrix4;
+use crate::pattern::{pattern, Pattern, PatternKind};
+use crate::render::{Camera, Scene};
+use crate::shapes::sphere;
+use crate::tuple::{Point3, Vector3};
+use crate::yaml::{self, Node, Value};
+use crate::Float;
+use std::collections::HashMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SceneError {
+    /// Empty when the scene did not come from a file.
+    pub file: String,
+    /// 0 when the error is not about a particular line.
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for SceneError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match (self.file.is_empty(), self.line) {
+            (true, 0) => write!(f, "{}", self.message),
+            (true, line) => write!(f, "line {}: {}", line, self.message),
+            (false, 0) => write!(f, "{}: {}", self.file, self.message),
+            (false, line) => write!(f, "{}:{}: {}", self.file, line, self.message),
+        }
+    }
+}
+
+impl From<yaml::Error> for SceneError {
+    fn from(e: yaml::Error) -> SceneError {
+        SceneError {
+            file: String::new(),
+            line: e.line,
+            message: e.message,
+        }
+    }
+}
+
+fn fail<T>(node: &Node, message: String) -> Result<T, SceneError> {
+    Err(SceneError {
+        file: String::new(),
+        line: node.line,
+        message,
+    })
+}
+
+fn describe(node: &Node) -> String {
+    match &node.value {
+        Value::Scalar(s) => format!("`{}`", s),
+        Value::List(_) => String::from("a list"),
+        Value::Map(_) => String::from("a mapping"),
+    }
+}
+
+fn text(node: &Node) -> Result<&str, SceneError> {
+    match &node.value {
+        Value::Scalar(s) => Ok(s),
+        _ => fail(node, format!("expected a name, found {}", describe(node))),
+    }
+}
+
+fn items(node: &Node) -> Result<&[Node], SceneError> {
+    match &node.value {
+        Value::List(items) => Ok(items),
+        _ => fail(node, format!("expected a list, found {}", describe(node))),
+    }
+}
+
+/// The entries of a mapping, rejecting keys outside `allowed`.
+fn entries<'a>(
+    node: &'a Node,
+    what: &str,
+    allowed: &[&str],
+) -> Result<&'a [(String, Node)], SceneError> {
+    let entries = match &node.value {
+        Value::Map(entries) => entries,
+        _ => return fail(node, format!("expected {}, found {}", what, describe(node))),
+    };
+    for (key, value) in entries {
+        if !allowed.contains(&key.as_str()) {
+            return fail(
+                value,
+                format!(
+                    "unknown key `{}` in {}; expected one of: {}",
+                    key,
+                    what,
+                    allowed.join(", ")
+                ),
+            );
+        }
+    }
+    Ok(entries)
+}
+
+fn get<'a>(entries: &'a [(String, Node)], key: &str) -> Option<&'a Node> {
+    entries.iter().find(|(k, _)| k == key).map(|(_, v)| v)
+}
+
+fn require<'a>(
+    node: &Node,
+    entries: &'a [(String, Node)],
+    key: &str,
+) -> Result<&'a Node, SceneError> {
+    get(entries, key).map_or_else(|| fail(node, format!("missing `{}`", key)), Ok)
+}
+
+struct Loader {
+    defines: HashMap<String, Node>,
+    variables: HashMap<String, Float>,
+    /// Files being loaded, outermost first, as (canonical path, name to
+    /// show), for detecting include cycles.
+    files: Vec<(PathBuf, String)>,
+}
+
+impl Loader {
+    /// A name given where a value is expected stands for its definition.
+    fn resolve<'a>(&'a self, node: &'a Node) -> &'a Node {
+        match &node.value {
+            Value::Scalar(name) => self.defines.get(name).unwrap_or(node),
+            _ => node,
+        }
+    }
+
+    fn number(&self, node: &Node) -> Result<Float, SceneError> {
+        let s = text(node)?;
+        expr::eval(s, |name| self.variables.get(name).copied()).or_else(|e| fail(node, e))
+    }
+
+    fn numbers<const N: usize>(&self, node: &Node) -> Result<[Float; N], SceneError> {
+        let items = items(self.resolve(node))?;
+        if items.len() != N {
+            return fail(
+                node,
+                format!("expected {} numbers, found {}", N, items.len()),
+            );
+        }
+        let mut out = [0.; N];
+        for (o, item) in out.iter_mut().zip(items) {
+            *o = self.number(item)?;
+        }
+        Ok(out)
+    }
+
+    fn color(&self, node: &Node) -> Result<Color, SceneError> {
+        let [r, g, b] = self.numbers(node)?;
+        Ok(Color { r, g, b })
+    }
+
+    fn point(&self, node: &Node) -> Result<Point3, SceneError> {
+        let [x, y, z] = self.numbers(node)?;
+        Ok(Point3::new(x, y, z))
+    }
+
+    /// Applies one transform step after `m`.
+    fn step(&self, m: Matrix4, node: &Node) -> Result<Matrix4, SceneError> {
+        let items = items(node)?;
+        let (op, args) = match items.split_first() {
+            Some((op, args)) => (text(op)?, args),
+            None => return fail(node, String::from("empty transform step")),
+        };
+        let arity = match op {
+            "translate" | "scale" => 3,
+            "rotate-x" | "rotate-y" | "rotate-z" => 1,
+            "rotate" => 4,
+            "shear" | "reflect" => 6,
+            "matrix" => 16,
+            _ => {
+                return fail(
+                    node,
+                    format!(
+                        "unknown transform `{}`; expected translate, scale, \
+                         rotate-x, rotate-y, rotate-z, rotate, shear, reflect \
+                         or matrix",
+                        op
+                    ),
+                )
+            }
+        };
+        if args.len() != arity {
+            return fail(
+                node,
+                format!("`{}` takes {} numbers, found {}", op, arity, args.len()),
+            );
+        }
+        let a = args
+            .iter()
+            .map(|n| self.number(n))
+            .collect::<Result<Vec<Float>, SceneError>>()?;
+        // the axis of `rotate` and the normal of `reflect`
+        let direction = match op {
+            "rotate" => Some(Vector3::new(a[0], a[1], a[2])),
+            "reflect" => Some(Vector3::new(a[3], a[4], a[5])),
+            _ => None,
+        };
+        if direction.is_some_and(|d| d.norm() == 0.) {
+            return fail(node, format!("`{}` needs a non-zero direction", op));
+        }
+        Ok(match op {
+            "translate" => m.translate(a[0], a[1], a[2]),
+            "scale" => m.scale(a[0], a[1], a[2]),
+            "rotate-x" => m.rotate_x(a[0]),
+            "rotate-y" => m.rotate_y(a[0]),
+            "rotate-z" => m.rotate_z(a[0]),
+            "rotate" => m.rotate_axis(Vector3::new(a[0], a[1], a[2]), a[3]),
+            "shear" => m.shear(a[0], a[1], a[2], a[3], a[4], a[5]),
+            "reflect" => m.reflect(
+                Point3::new(a[0], a[1], a[2]),
+                Vector3::new(a[3], a[4], a[5]),
+            ),
+            _ => {
+                let mut rows = [[0.; 4]; 4];
+                for (i, v) in a.iter().enumerate() {
+                    rows[i / 4][i % 4] = *v;
+                }
+                Matrix4(rows) * m
+            }
+        })
+    }
+
+    /// Like `transform`, but also rejects transforms with no inverse.
+    fn invertible(&self, node: &Node) -> Result<Matrix4, SceneError> {
+        let m = self.transform(node)?;
+        if m.try_inverse().is_none() {
+            return fail(node, String::from("transform is not invertible"));
+        }
+        Ok(m)
+    }
+
+    fn transform(&self, node: &Node) -> Result<Matrix4, SceneError> {
+        let mut m = Matrix4::identity();
+        for item in items(self.resolve(node))? {
+            if let Value::Scalar(name) = &item.value {
+                // definitions are flattened when made, so one level is enough
+                match self.defines.get(name).map(|d| &d.value) {
+                    Some(Value::List(sub)) => {
+                        for s in sub {
+                            m = self.step(m, s)?;
+                        }
+                    }
+                    _ => return fail(item, format!("unknown transform `{}`", name)),
+                }
+            } else {
+                m = self.step(m, item)?;
+            }
+        }
+        Ok(m)
+    }
+
+    fn pattern(&self, node: &Node) -> Result<Pattern, SceneError> {
+        let node = self.resolve(node);
+        let e = entries(node, "a pattern", &["type", "colors", "transform"])?;
+        let kind_node = require(node, e, "type")?;
+        let kind = match text(kind_node)? {
+            "stripes" => PatternKind::Stripes,
+            "gradient" => PatternKind::Gradient,
+            "rings" => PatternKind::Rings,
+            "checkers" => PatternKind::Checkers,
+            other => {
+                return fail(
+                    kind_node,
+                    format!(
+                        "unknown pattern `{}`; expected stripes, gradient, rings or checkers",
+                        other
+                    ),
+                )
+            }
+        };
+        let colors_node = require(node, e, "colors")?;
+        let colors = items(self.resolve(colors_node))?;
+        if colors.len() != 2 {
+            return fail(colors_node, String::from("a pattern takes two colors"));
+        }
+        let mut p = pattern(kind, self.color(&colors[0])?, self.color(&colors[1])?);
+        if let Some(t) = get(e, "transform") {
+            p = p.with_transform(self.invertible(t)?);
+        }
+        Ok(p)
+    }
+
+    fn material(&self, node: &Node) -> Result<Material, SceneError> {
+        let node = self.resolve(node);
+        let e = entries(
+            node,
+            "a material",
+            &[
+                "color",
+                "pattern",
+                "ambient",
+                "diffuse",
+                "specular",
+                "shininess",
+            ],
+        )?;
+        let mut m = Material::default();
+        for (key, value) in e {
+            match key.as_str() {
+                "color" => m.color = self.color(value)?,
+                "pattern" => m.pattern = Some(self.pattern(value)?),
+                "ambient" => m.ambient = self.number(value)?,
+                "diffuse" => m.diffuse = self.number(value)?,
+                "specular" => m.specular = self.number(value)?,
+                _ => m.shininess = self.number(value)?,
+            }
+        }
+        Ok(m)
+    }
+
+    fn define(&mut self, node: &Node) -> Result<(), SceneError> {
+        let e = entries(node, "a definition", &["define", "extend", "value"])?;
+        let name = text(require(node, e, "define")?)?.to_string();
+        let value_node = require(node, e, "value")?;
+        if let Value::Scalar(s) = &value_node.value {
+            if !self.defines.contains_key(s) {
+                if get(e, "extend").is_some() {
+                    return fail(node, String::from("only mappings can be extended"));
+                }
+                let v = self.number(value_node)?;
+                self.defines.remove(&name);
+                self.variables.insert(name, v);
+                return Ok(());
+            }
+        }
+        let mut value = self.resolve(value_node).clone();
+        if let Some(parent_node) = get(e, "extend") {
+            let parent_name = text(parent_node)?;
+            let parent = match self.defines.get(parent_name) {
+                Some(p) => p,
+                None => return fail(parent_node, format!("unknown definition `{}`", parent_name)),
+            };
+            value = match (&parent.value, &value.value) {
+                (Value::Map(base), Value::Map(own)) => {
+                    let mut merged: Vec<(String, Node)> = base
+                        .iter()
+                        .filter(|(k, _)| get(own, k).is_none())
+                        .cloned()
+                        .collect();
+                    merged.extend(own.iter().cloned());
+                    Node {
+                        line: value.line,
+                        value: Value::Map(merged),
+                    }
+                }
+                _ => return fail(parent_node, String::from("only mappings can be extended")),
+            };
+        }
+        if let Value::List(steps) = &value.value {
+            // expand named transforms now, so later uses need no recursion
+            let mut flat = vec![];
+            for s in steps {
+                match (&s.value, self.resolve(s)) {
+                    (
+                        Value::Scalar(_),
+                        Node {
+                            value: Value::List(sub),
+                            ..
+                        },
+                    ) => flat.extend(sub.iter().cloned()),
+                    _ => flat.push(s.clone()),
+                }
+            }
+            value.value = Value::List(flat);
+        }
+        self.variables.remove(&name);
+        self.defines.insert(name, value);
+        Ok(())
+    }
+
+    fn include(&mut self, node: &Node, dir: &Path, scene: &mut Scene) -> Result<(), SceneError> {
+        let e = entries(node, "an include", &["include"])?;
+        let name_node = require(node, e, "include")?;
+        let path = dir.join(text(name_node)?);
+        let shown = path.display().to_string();
+        let cannot = |e: std::io::Error| SceneError {
+            file: String::new(),
+            line: name_node.line,
+            message: format!("cannot include {}: {}", shown, e),
+        };
+        let canonical = path.canonicalize().map_err(cannot)?;
+        if let Some(i) = self.files.iter().position(|(f, _)| *f == canonical) {
+            let mut cycle: Vec<&str> = self.files[i..].iter().map(|(_, n)| n.as_str()).collect();
+            cycle.push(&shown);
+            return fail(name_node, format!("include cycle: {}", cycle.join(" -> ")));
+        }
+        let source = std::fs::read_to_string(&path).map_err(cannot)?;
+        self.file(&path, canonical, &source, scene)
+    }
+
+    /// Runs the items of one file, attributing errors in it to `path`.
+    fn file(
+        &mut self,
+        path: &Path,
+        canonical: PathBuf,
+        source: &str,
+        scene: &mut Scene,
+    ) -> Result<(), SceneError> {
+        let shown = path.display().to_string();
+        self.files.push((canonical, shown.clone()));
+        let dir = path.parent().unwrap_or_else(|| Path::new(""));
+        let result = self.run(source, dir, scene).map_err(|e| {
+            if e.file.is_empty() {
+                SceneError { file: shown, ..e }
+            } else {
+                e
+            }
+        });
+        self.files.pop();
+        result
+    }
+
+    fn run(&mut self, source: &str, dir: &Path, scene: &mut Scene) -> Result<(), SceneError> {
+        let root = yaml::parse(source)?;
+        let list = match &root.value {
+            Value::List(items) => items,
+            _ => {
+                return fail(
+                    &root,
+                    String::from("a scene file is a list of `add`, `define` and `include` items"),
+                )
+            }
+        };
+        for item in list {
+            let e = match &item.value {
+                Value::Map(e) => e,
+                _ => {
+                    return fail(
+                        item,
+                        format!(
+                            "expected an `add`, `define` or `include` item, found {}",
+                            describe(item)
+                        ),
+                    )
+                }
+            };
+            if get(e, "define").is_some() {
+                self.define(item)?;
+            } else if get(e, "add").is_some() {
+                self.add(item, scene)?;
+            } else if get(e, "include").is_some() {
+                self.include(item, dir, scene)?;
+            } else {
+                return fail(
+                    item,
+                    String::from("expected an `add`, `define` or `include` item"),
+                );
+            }
+        }
+        Ok(())
+    }
+
+    fn add(&self, node: &Node, scene: &mut Scene) -> Result<(), SceneError> {
+        let kind_node = match &node.value {
+            Value::Map(e) => require(node, e, "add")?,
+            _ => unreachable!("checked by the caller"),
+        };
+        match text(kind_node)? {
+            "camera" => {
+                let e = entries(
+                    node,
+                    "a camera",
+                    &["add", "eye", "wall-z", "wall-width", "wall-height"],
+                )?;
+                let c = &mut scene.camera;
+                for (key, value) in e {
+                    match key.as_str() {
+                        "eye" => c.eye = self.point(value)?,
+                        "wall-z" => c.wall_z = self.number(value)?,
+                        "wall-width" => c.wall_width = self.number(value)?,
+                        "wall-height" => c.wall_height = self.number(value)?,
+                        _ => {}
+                    }
+                }
+            }
+            "light" => {
+                let e = entries(node, "a light", &["add", "at", "intensity"])?;
+                let at = self.point(require(node, e, "at")?)?;
+                let intensity = match get(e, "intensity") {
+                    Some(i) => self.color(i)?,
+                    None => Color {
+                        r: 1.,
+                        g: 1.,
+                        b: 1.,
+                    },
+                };
+                scene.lights.push(point_light(at, intensity));
+            }
+            "sphere" => {
+                let e = entries(node, "a sphere", &["add", "transform", "material"])?;
+                let transform = match get(e, "transform") {
+                    Some(t) => self.invertible(t)?,
+                    None => Matrix4::identity(),
+                };
+                let material = match get(e, "material") {
+                    Some(m) => self.material(m)?,
+                    None => Material::default(),
+                };
+                scene
+                    .shapes
+                    .push(Box::new(sphere(transform).with_material(material)));
+            }
+            other => {
+                return fail(
+                    kind_node,
+                    format!("cannot add `{}`; expected camera, light or sphere", other),
+                )
+            }
+        }
+        Ok(())
+    }
+}
+
+fn loader() -> Loader {
+    Loader {
+        defines: HashMap::new(),
+        variables: HashMap::new(),
+        files: vec![],
+    }
+}
+
+fn empty_scene() -> Scene {
+    Scene {
+        camera: Camera::default(),
+        lights: vec![],
+        shapes: vec![],
+    }
+}
+
+fn scalar<T: ToString>(v: T) -> Node {
+    Node {
+        line: 0,
+        value: Value::Scalar(v.to_string()),
+    }
+}
+
+fn list(items: Vec<Node>) -> Node {
+    Node {
+        line: 0,
+        value: Value::List(items),
+    }
+}
+
+fn map(entries: Vec<(&str, Node)>) -> Node {
+    Node {
+        line: 0,
+        value: Value::Map(
+            entries
+                .into_iter()
+                .map(|(k, v)| (k.to_string(), v))
+                .collect(),
+        ),
+    }
+}
+
+// Floats format as the shortest text that parses back to the same value,
+// so exported numbers round-trip exactly
+fn triple(x: Float, y: Float, z: Float) -> Node {
+    list(vec![scalar(x), scalar(y), scalar(z)])
+}
+
+fn color_node(c: &Color) -> Node {
+    triple(c.r, c.g, c.b)
+}
+
+fn transform_node(m: &Matrix4) -> Node {
+    let mut step = vec![scalar("matrix")];
+    step.extend(m.0.iter().flatten().map(scalar));
+    list(vec![list(step)])
+}
+
+fn material_node(m: &Material) -> Node {
+    let mut entries = vec![("color", color_node(&m.color))];
+    if let Some(p) = &m.pattern {
+        let kind = match p.kind {
+            PatternKind::Stripes => "stripes",
+            PatternKind::Gradient => "gradient",
+            PatternKind::Rings => "rings",
+            PatternKind::Checkers => "checkers",
+        };
+        entries.push((
+            "pattern",
+            map(vec![
+                ("type", scalar(kind)),
+                ("colors", list(vec![color_node(&p.a), color_node(&p.b)])),
+                ("transform", transform_node(&p.transform())),
+            ]),
+        ));
+    }
+    entries.extend(vec![
+        ("ambient", scalar(m.ambient)),
+        ("diffuse", scalar(m.diffuse)),
+        ("specular", scalar(m.specular)),
+        ("shininess", scalar(m.shininess)),
+    ]);
+    map(entries)
+}
+
+/// The scene as the items of a scene file, with every value spelled out.
+fn scene_node(scene: &Scene) -> Node {
+    let c = &scene.camera;
+    let mut items = vec![map(vec![
+        ("add", scalar("camera")),
+        ("eye", triple(c.eye.x, c.eye.y, c.eye.z)),
+        ("wall-z", scalar(c.wall_z)),
+        ("wall-width", scalar(c.wall_width)),
+        ("wall-height", scalar(c.wall_height)),
+    ])];
+    for l in &scene.lights {
+        let p = &l.position;
+        items.push(map(vec![
+            ("add", scalar("light")),
+            ("at", triple(p.x, p.y, p.z)),
+            ("intensity", color_node(&l.intensity)),
+        ]));
+    }
+    for s in &scene.shapes {
+        items.push(map(vec![
+            ("add", scalar(s.kind())),
+            ("transform", transform_node(&s.transform())),
+            ("material", material_node(s.material())),
+        ]));
+    }
+    list(items)
+}
+
+/// Writes `scene` as a scene file that `parse` reads back as an
+/// equivalent scene: same camera, lights, transforms and materials.
+pub fn to_yaml(scene: &Scene) -> String {
+    yaml::to_string(&scene_node(scene))
+}
+
+/// The same data as `to_yaml`, as canonical JSON for other tools.
+pub fn to_json(scene: &Scene) -> String {
+    json::to_string(&scene_node(scene))
+}
+
+/// Writes `to_json` if `path` ends in `.json`, otherwise `to_yaml`.
+pub fn save(scene: &Scene, path: &str) -> std::io::Result<()> {
+    let text = if path.to_ascii_lowercase().ends_with(".json") {
+        to_json(scene)
+    } else {
+        to_yaml(scene)
+    };
+    let tmp = format!("{}.tmp", path);
+    std::fs::write(&tmp, text)?;
+    std::fs::rename(&tmp, path)
+}
+
+/// Builds a scene from the text of a scene file. Includes are read
+/// relative to the working directory.
+pub fn parse(source: &str) -> Result<Scene, SceneError> {
+    let mut scene = empty_scene();
+    loader().run(source, Path::new(""), &mut scene)?;
+    Ok(scene)
+}
+
+pub fn load(path: &str) -> Result<Scene, SceneError> {
+    let cannot = |e: std::io::Error| SceneError {
+        file: path.to_string(),
+        line: 0,
+        message: e.to_string(),
+    };
+    let canonical = Path::new(path).canonicalize().map_err(cannot)?;
+    let source = std::fs::read_to_string(path).map_err(cannot)?;
+    let mut scene = empty_scene();
+    loader().file(Path::new(path), canonical, &source, &mut scene)?;
+    Ok(scene)
+}
+
+/// What the renderer shows without a scene file: one red sphere.
+pub fn demo() -> Scene {
+    // TODO: determine wall dims from canvas dims
+    let red = material(Color {
+        r: 1.,
+        g: 0.,
+        b: 0.,
+    });
+    Scene {
+        shapes: vec![Box::new(sphere(Matrix4::IDENTITY).with_material(red))],
+        ..empty_scene()
+    }
+}
+
+#[cfg(test)]
+mod scene_tests {
+    use super::*;
+    use crate::consts::PI;
+    use crate::material::material;
+    use crate::matrix::matrix::{chain, rotate_x, rotate_y, rotate_z, scale, shear, translation};
+    use crate::shapes::sphere_unit;
+
+    fn error(source: &str) -> SceneError {
+        match parse(source) {
+            Ok(_) => panic!("expected an error"),
+            Err(e) => e,
+        }
+    }
+
+    #[test]
+    fn full_scene() {
+        let s = parse(
+            "\
+- define: glossy
+  value:
+    specular: 1
+    shininess: 300
+- define: red-glossy
+  extend: glossy
+  value:
+    color: [1, 0, 0]
+    shininess: 50
+- define: lift
+  value:
+    - [scale, 1, 0.5, 1]
+    - [translate, 0, 1, 0]
+- add: camera
+  eye: [0, 1, -8]
+  wall-width: 10
+- add: light
+  at: [-10, 10, -10]
+  intensity: [0.5, 0.5, 0.5]
+- add: sphere
+  material: red-glossy
+  transform:
+    - lift
+    - [rotate-y, 1.5707963267948966]
+- add: sphere
+  material:
+    pattern:
+      type: stripes
+      colors: [[1, 1, 1], [0, 0, 0]]
+      transform: [[scale, 0.25, 0.25, 0.25]]
+",
+        )
+        .expect("valid scene");
+        assert!(s.camera.eye.eq(&Point3::new(0., 1., -8.)));
+        assert_eq!((s.camera.wall_width, s.camera.wall_height), (10., 7.));
+        assert_eq!(s.lights.len(), 1);
+        assert!(
+            s.lights[0].intensity
+                == Color {
+                    r: 0.5,
+                    g: 0.5,
+                    b: 0.5
+                }
+        );
+        assert_eq!(s.shapes.len(), 2);
+
+        let m = s.shapes[0].material();
+        assert!(
+            m.color
+                == Color {
+                    r: 1.,
+                    g: 0.,
+                    b: 0.
+                }
+        );
+        assert_eq!((m.specular, m.shininess, m.diffuse), (1., 50., 0.9));
+        let expected = chain(vec![
+            scale(1., 0.5, 1.),
+            translation(0., 1., 0.),
+            rotate_y(PI / 2.),
+        ]);
+        assert_eq!(s.shapes[0].transform(), expected);
+
+        let p = s.shapes[1].material().pattern.as_ref().expect("pattern");
+        assert_eq!(p.kind, PatternKind::Stripes);
+        assert_eq!(p.transform(), scale(0.25, 0.25, 0.25));
+        assert_eq!(s.shapes[1].transform(), Matrix4::IDENTITY);
+    }
+
+    #[test]
+    fn axis_steps() {
+        let s = parse(
+            "- add: sphere\n  transform:\n    - [rotate, 0, 0, 2, pi / 2]\n    \
+             - [reflect, 0, 1, 0, 0, 3, 0]\n",
+        )
+        .expect("valid scene");
+        let expected = translation(0., 2., 0.) * scale(1., -1., 1.) * rotate_z(PI / 2.);
+        assert_eq!(s.shapes[0].transform(), expected);
+        let e = error("- add: sphere\n  transform: [[rotate, 1, 0, 0]]\n");
+        assert_eq!(e.message, "`rotate` takes 4 numbers, found 3");
+        let e = error("- add: sphere\n  transform: [[rotate, 0, 0, 0, 1]]\n");
+        assert_eq!(e.message, "`rotate` needs a non-zero direction");
+        let e = error("- add: sphere\n  transform: [[reflect, 1, 2, 3, 0, 0, 0]]\n");
+        assert_eq!(e.message, "`reflect` needs a non-zero direction");
+    }
+
+    #[test]
+    fn empty_scene() {
+        let s = parse("# nothing here\n").expect("valid scene");
+        assert!(s.shapes.is_empty() && s.lights.is_empty());
+    }
+
+    #[test]
+    fn errors() {
+        let e = error("- add: sphere\n  transform:\n    - [translate, 1, 2]\n");
+        assert_eq!(e.line, 3);
+        assert_eq!(e.message, "`translate` takes 3 numbers, found 2");
+        assert_eq!(
+            e.to_string(),
+            "line 3: `translate` takes 3 numbers, found 2"
+        );
+
+        let e = error("- add: light\n  at: [1, 2, x]\n");
+        assert_eq!((e.line, e.message.as_str()), (2, "unknown variable `x`"));
+
+        let e = error("- add: sphere\n\n  colour: [1, 0, 0]\n");
+        assert_eq!(e.line, 3);
+        assert!(e.message.starts_with("unknown key `colour` in a sphere"));
+
+        let e = error("- add: cube\n");
+        assert_eq!(e.line, 1);
+        let e = error("- define: a\n  extend: b\n  value:\n    color: [1, 1, 1]\n");
+        assert_eq!((e.line, e.message.as_str()), (2, "unknown definition `b`"));
+        let e = error("- add: light\n");
+        assert_eq!((e.line, e.message.as_str()), (1, "missing `at`"));
+        let e = error("- add: sphere\n  transform: [spin]\n");
+        assert_eq!(
+            (e.line, e.message.as_str()),
+            (2, "unknown transform `spin`")
+        );
+        // a definition cannot refer to itself
+        let e = error("- define: t\n  value: [t]\n- add: sphere\n  transform: t\n");
+        assert_eq!(e.line, 2);
+        let e = error("- add: sphere\n  transform:\n    - [scale, 1, 0, 1]\n");
+        assert_eq!(
+            (e.line, e.message.as_str()),
+            (3, "transform is not invertible")
+        );
+        let e = error(
+            "- add: sphere\n  material:\n    pattern:\n      type: rings\n      \
+             colors: [[1, 1, 1], [0, 0, 0]]\n      transform: [[scale, 0, 0, 0]]\n",
+        );
+        assert_eq!(e.line, 6);
+        // syntax errors keep their line
+        assert_eq!(error("- add: sphere\n   oops: 1\n").line, 2);
+    }
+
+    #[test]
+    fn variables() {
+        let s = parse(
+            "\
+- define: spacing
+  value: 2.5
+- define: half
+  value: spacing / 2
+- define: column
+  value:
+    - [rotate-y, pi / 4]
+    - [translate, spacing * 2, 0, -half]
+- add: sphere
+  transform: column
+- add: light
+  at: [-spacing, 10 - 2 * half, 0]
+",
+        )
+        .expect("valid scene");
+        let expected = chain(vec![rotate_y(PI / 4.), translation(5., 0., -1.25)]);
+        assert_eq!(s.shapes[0].transform(), expected);
+        assert!(s.lights[0].position.eq(&Point3::new(-2.5, 7.5, 0.)));
+
+        let e = error("- add: light\n  at: [0, 0, width / 2]\n");
+        assert_eq!(
+            (e.line, e.message.as_str()),
+            (2, "unknown variable `width`")
+        );
+        let e = error("- define: n\n  value: 1 +\n");
+        assert_eq!(e.line, 2);
+        let deep = format!("- define: n\n  value: {}1\n", "(".repeat(10_000));
+        let e = error(&deep);
+        assert_eq!(
+            (e.line, e.message.as_str()),
+            (2, "expression is nested too deeply")
+        );
+    }
+
+    const SPHERES: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/scenes/spheres.yml");
+
+    fn scene_dir(name: &str) -> std::path::PathBuf {
+        let dir = std::env::temp_dir().join(format!("{}_{}", name, std::process::id()));
+        std::fs::create_dir_all(dir.join("lib")).expect("temp dir writable");
+        dir
+    }
+
+    fn write(dir: &Path, name: &str, text: &str) -> String {
+        let path = dir.join(name);
+        std::fs::write(&path, text).expect("temp dir writable");
+        path.display().to_string()
+    }
+
+    #[test]
+    fn includes() {
+        let dir = scene_dir("scene_includes");
+        write(
+            &dir,
+            "lib/materials.yml",
+            "- include: common.yml\n- define: red\n  value:\n    color: [1, 0, 0]\n",
+        );
+        write(&dir, "lib/common.yml", "- define: spacing\n  value: 3\n");
+        let main = write(
+            &dir,
+            "main.yml",
+            "\
+- include: lib/materials.yml
+- include: lib/common.yml
+- add: sphere
+  material: red
+  transform: [[translate, spacing, 0, 0]]
+",
+        );
+        let s = load(&main).expect("valid scene");
+        assert!(
+            s.shapes[0].material().color
+                == Color {
+                    r: 1.,
+                    g: 0.,
+                    b: 0.
+                }
+        );
+        assert_eq!(s.shapes[0].transform(), translation(3., 0., 0.));
+
+        // errors name the file they are in
+        write(&dir, "lib/broken.yml", "- add: light\n  at: [0, 0]\n");
+        let main = write(&dir, "broken.yml", "- include: lib/broken.yml\n");
+        let e = load(&main).err().expect("invalid scene");
+        assert!(e.file.ends_with("broken.yml") && e.file.contains("lib"));
+        assert_eq!(e.line, 2);
+
+        let main = write(&dir, "missing.yml", "\n- include: lib/nope.yml\n");
+        let e = load(&main).err().expect("invalid scene");
+        assert!(e.file.ends_with("missing.yml"));
+        assert_eq!(e.line, 2);
+        assert!(e.message.starts_with("cannot include"));
+        std::fs::remove_dir_all(&dir).expect("removable");
+    }
+
+    #[test]
+    fn include_cycles() {
+        let dir = scene_dir("scene_cycles");
+        write(&dir, "lib/a.yml", "- include: b.yml\n");
+        write(
+            &dir,
+            "lib/b.yml",
+            "- define: x\n  value: 1\n- include: a.yml\n",
+        );
+        let main = write(&dir, "main.yml", "- include: lib/a.yml\n");
+        let e = load(&main).err().expect("cycle");
+        assert!(e.file.ends_with("b.yml"));
+        assert_eq!(e.line, 3);
+        let cycle: Vec<&str> = e
+            .message
+            .trim_start_matches("include cycle: ")
+            .split(" -> ")
+            .collect();
+        assert_eq!(cycle.len(), 3);
+        assert!(cycle[0].ends_with("a.yml") && cycle[2] == cycle[0]);
+        assert!(cycle[1].ends_with("b.yml"));
+
+        let main = write(&dir, "self.yml", "- include: self.yml\n");
+        let e = load(&main).err().expect("cycle");
+        assert_eq!(e.line, 1);
+        assert!(e.message.starts_with("include cycle: "));
+        std::fs::remove_dir_all(&dir).expect("removable");
+    }
+
+    fn programmatic() -> Scene {
+        let mut m = material(Color {
+            r: 0.1,
+            g: 0.2,
+            b: 0.3,
+        });
+        m.shininess = 42.;
+        m.pattern = Some(
+            pattern(PatternKind::Rings, Color::default(), m.color.clone())
+                .with_transform(rotate_z(0.3)),
+        );
+        Scene {
+            camera: Camera {
+                eye: Point3::new(1., 2., -7.),
+                ..Camera::default()
+            },
+            lights: vec![point_light(
+                Point3::new(-10., 10., -10.),
+                Color {
+                    r: 1. / 3.,
+                    g: 1.,
+                    b: 1.,
+                },
+            )],
+            shapes: vec![
+                Box::new(sphere_unit()),
+                Box::new(
+                    sphere(chain(vec![
+                        shear(1., 0., 0.5, 0., 0., 0.),
+                        rotate_x(1.),
+                        translation(0.1, 0.2, 0.3),
+                    ]))
+                    .with_material(m),
+                ),
+            ],
+        }
+    }
+
+    #[test]
+    fn round_trip() {
+        let s = programmatic();
+        let text = to_yaml(&s);
+        let back = parse(&text).expect("exported scene loads");
+        assert!(back.camera.eye.eq(&s.camera.eye));
+        assert_eq!(back.camera.wall_z, s.camera.wall_z);
+        assert!(back.lights[0].intensity == s.lights[0].intensity);
+        assert_eq!(back.shapes.len(), 2);
+        for (a, b) in s.shapes.iter().zip(back.shapes.iter()) {
+            assert_eq!(a.kind(), b.kind());
+            assert_eq!(a.transform(), b.transform());
+            let (ma, mb) = (a.material(), b.material());
+            assert!(ma.color == mb.color);
+            assert_eq!(
+                (ma.ambient, ma.diffuse, ma.specular, ma.shininess),
+                (mb.ambient, mb.diffuse, mb.specular, mb.shininess)
+            );
+            assert_eq!(ma.pattern.is_some(), mb.pattern.is_some());
+        }
+        let (pa, pb) = (
+            s.shapes[1].material().pattern.as_ref().expect("pattern"),
+            back.shapes[1].material().pattern.as_ref().expect("pattern"),
+        );
+        assert_eq!((pa.kind, pa.transform()), (pb.kind, pb.transform()));
+        assert!(pa.a == pb.a && pa.b == pb.b);
+
+        assert_eq!(to_yaml(&back), text);
+        assert_eq!(to_json(&back), to_json(&s));
+        assert!(to_json(&s).starts_with("[\n  {\n    \"add\": \"camera\",\n"));
+    }
+
+    #[test]
+    fn save_and_load() {
+        let dir = scene_dir("scene_save");
+        let original = load(SPHERES).expect("example scene loads");
+        for name in ["saved.yml", "saved.json"].iter() {
+            let path = dir.join(name).display().to_string();
+            save(&original, &path).expect("temp dir writable");
+            let text = std::fs::read_to_string(&path).expect("just written");
+            if name.ends_with(".json") {
+                assert_eq!(text, to_json(&original));
+            } else {
+                let back = load(&path).expect("saved scene loads");
+                assert_eq!(to_json(&back), to_json(&original));
+            }
+        }
+        std::fs::remove_dir_all(&dir).expect("removable");
+    }
+
+    #[test]
+    fn example_file() {
+        let s = load(SPHERES).expect("example scene loads");
+        assert!(!s.shapes.is_empty() && !s.lights.is_empty());
+        let missing = concat!(env!("CARGO_MANIFEST_DIR"), "/scenes/missing.yml");
+        let e = load(missing).err().expect("no such file");
+        assert_eq!(e.file, missing);
+    }
+}
@@ -1,44 +1,105 @@
-use crate::matrix::matrix::{Matrix4, M4};
-use crate::ray::Ray;
-use crate::tuple::Tuple;
+use crate::material::Material;
+use crate::matrix::matrix::Matrix4;
+use crate::ray::{Ray, RayPacket};
+use crate::simd::Float4;
+use crate::tuple::{Normal3, Point3};
+use crate::Float;
+use std::fmt;
 use std::sync::atomic::{AtomicIsize, Ordering};
 
-pub trait Shape {
-    fn intersects(&self, t: Ray) -> Vec<Intersection>;
+/// Shapes are shared read-only between render threads.
+pub trait Shape: Send + Sync {
+    fn intersects(&self, t: Ray) -> Vec<Intersection<'_>>;
+    /// What `hit` finds for each ray of a packet: the nearest
+    /// non-negative `t`. The default traces the rays one at a time.
+    fn hit_packet(&self, rays: &RayPacket) -> [Option<Float>; 4] {
+        [0, 1, 2, 3].map(|i| hit(self.intersects(rays.ray(i))).map(|h| h.t))
+    }
     fn id(&self) -> isize;
+    /// World-space unit normal at a point on the surface.
+    fn normal_at(&self, p: Point3) -> Normal3;
+    fn material(&self) -> &Material;
+    /// Object-to-world transform.
+    fn transform(&self) -> Matrix4;
+    /// World-to-object transform.
+    fn inverse(&self) -> Matrix4;
+    /// What scene files call this kind of shape.
+    fn kind(&self) -> &'static str;
+}
+
+/// A transform with no inverse, which would squash a shape flat.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SingularTransform {
+    /// Boxed to keep `Result`s small.
+    pub transform: Box<Matrix4>,
+}
+
+impl fmt::Display for SingularTransform {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "transform is not invertible: {:?}", self.transform)
+    }
 }
 
 static _MAX_SHAPE_ID: AtomicIsize = AtomicIsize::new(0);
 
 pub struct Sphere {
     transform: Matrix4,
+    // used for every ray and normal, so worked out once in `set_transform`
+    inverse: Matrix4,
+    // the transpose of `inverse`, which carries object normals to world space
+    normal_matrix: Matrix4,
+    material: Material,
     id: isize,
 }
 
 pub fn sphere_unit() -> Sphere {
-    Sphere {
-        id: _MAX_SHAPE_ID.fetch_add(1, Ordering::SeqCst),
-        transform: M4::I,
-    }
+    sphere(Matrix4::IDENTITY)
 }
 
+/// Panics if `transform` is singular; see `try_sphere`.
 pub fn sphere(transform: Matrix4) -> Sphere {
-    Sphere {
-        id: _MAX_SHAPE_ID.fetch_add(1, Ordering::SeqCst),
-        transform,
+    match try_sphere(transform) {
+        Ok(s) => s,
+        Err(e) => panic!("{}", e),
     }
 }
 
-// impl Sphere {
-// }
+pub fn try_sphere(transform: Matrix4) -> Result<Sphere, SingularTransform> {
+    let mut s = Sphere {
+        id: _MAX_SHAPE_ID.fetch_add(1, Ordering::SeqCst),
+        transform: Matrix4::IDENTITY,
+        inverse: Matrix4::IDENTITY,
+        normal_matrix: Matrix4::IDENTITY,
+        material: Material::default(),
+    };
+    s.set_transform(transform)?;
+    Ok(s)
+}
+
+impl Sphere {
+    /// Leaves the sphere unchanged if `transform` is singular.
+    pub fn set_transform(&mut self, transform: Matrix4) -> Result<(), SingularTransform> {
+        let inverse = transform.try_inverse().ok_or(SingularTransform {
+            transform: Box::new(transform),
+        })?;
+        self.transform = transform;
+        self.inverse = inverse;
+        self.normal_matrix = inverse.transpose();
+        Ok(())
+    }
+    pub fn with_material(mut self, material: Material) -> Sphere {
+        self.material = material;
+        self
+    }
+}
 
 impl Shape for Sphere {
-    fn intersects(&self, r: Ray) -> Vec<Intersection> {
-        let r = r.transform(M4::invert(self.transform));
-        let sphere_to_ray = r.origin.subtract(Tuple::point(0., 0., 0.));
-        let a = r.direction.dot(r.direction.clone());
-        let b = 2. * r.direction.dot(sphere_to_ray.clone());
-        let c = sphere_to_ray.dot(sphere_to_ray.clone()) - 1.;
+    fn intersects(&self, r: Ray) -> Vec<Intersection<'_>> {
+        let r = r.transform(self.inverse);
+        let sphere_to_ray = r.origin - Point3::ORIGIN;
+        let a = r.direction.dot(r.direction);
+        let b = 2. * r.direction.dot(sphere_to_ray);
+        let c = sphere_to_ray.dot(sphere_to_ray) - 1.;
         let d = b * b - 4. * a * c;
         if d < 0. {
             vec![]
@@ -55,22 +116,61 @@ impl Shape for Sphere {
             ]
         }
     }
+    /// `intersects` for four rays at once, rounding the same way.
+    fn hit_packet(&self, rays: &RayPacket) -> [Option<Float>; 4] {
+        let r = rays.transform(self.inverse);
+        let dot = |u: &[Float4; 3], v: &[Float4; 3]| u[0] * v[0] + u[1] * v[1] + u[2] * v[2];
+        let a = dot(&r.direction, &r.direction);
+        let b = Float4::splat(2.) * dot(&r.direction, &r.origin);
+        let c = dot(&r.origin, &r.origin) - Float4::splat(1.);
+        let d = b * b - Float4::splat(4.) * a * c;
+        let root = d.sqrt();
+        let two_a = Float4::splat(2.) * a;
+        let near = (Float4::splat(0.) - b - root) / two_a;
+        let far = (Float4::splat(0.) - b + root) / two_a;
+        let (d, near, far) = (d.to_array(), near.to_array(), far.to_array());
+        [0, 1, 2, 3].map(|i| {
+            if d[i] < 0. {
+                None
+            } else if near[i] >= 0. {
+                Some(near[i])
+            } else if far[i] >= 0. {
+                Some(far[i])
+            } else {
+                None
+            }
+        })
+    }
     fn id(&self) -> isize {
         self.id
     }
+    fn normal_at(&self, p: Point3) -> Normal3 {
+        let object_normal = self.inverse * p - Point3::ORIGIN;
+        // as a vector, so the transposed translation row is ignored
+        Normal3::from(self.normal_matrix * object_normal).unit()
+    }
+    fn material(&self) -> &Material {
+        &self.material
+    }
+    fn transform(&self) -> Matrix4 {
+        self.transform
+    }
+    fn inverse(&self) -> Matrix4 {
+        self.inverse
+    }
+    fn kind(&self) -> &'static str {
+        "sphere"
+    }
 }
 
 #[derive(Clone)]
 pub struct Intersection<'a> {
-    t: f32,
-    object: &'a dyn Shape,
+    pub t: Float,
+    pub object: &'a dyn Shape,
 }
 
 pub fn hit(intersections: Vec<Intersection>) -> Option<Intersection> {
-    if intersections.first().is_none() {
-        return None;
-    }
-    let mut best_i = intersections.first().expect("must exist").clone();
+    let mut best_i = intersections.first()?.clone();
     for i in intersections {
         if best_i.t < 0. || (i.t >= 0. && i.t < best_i.t) {
             best_i = i.clone();
@@ -85,16 +185,18 @@ pub fn hit(intersections: Vec<Intersection>) -> Option<Intersection> {
 
 #[cfg(test)]
 mod sphere_tests {
-    use crate::matrix::matrix::{scale, translation};
-    use crate::ray::ray;
+    use crate::consts::FRAC_1_SQRT_2;
+    use crate::matrix::matrix::{rotate_z, scale, translation};
+    use crate::ray::{ray, RayPacket};
     use crate::shapes;
     use crate::shapes::{hit, Intersection, Shape};
-    use crate::tuple::Tuple;
+    use crate::tuple::{Normal3, Point3, Vector3};
+    use crate::Float;
 
     #[test]
     fn sphere_test_1() {
         let s = shapes::sphere_unit();
-        let r = ray(Tuple::point(0., 0., -5.), Tuple::vector(0., 0., 1.));
+        let r = ray(Point3::new(0., 0., -5.), Vector3::new(0., 0., 1.));
         let r_thru_s = s.intersects(r);
 
         assert_eq!(r_thru_s.len(), 2);
@@ -107,7 +209,7 @@ mod sphere_tests {
     #[test]
     fn sphere_test_2() {
         let s = shapes::sphere_unit();
-        let r = ray(Tuple::point(0., 1., -5.), Tuple::vector(0., 0., 1.));
+        let r = ray(Point3::new(0., 1., -5.), Vector3::new(0., 0., 1.));
         let r_thru_s = s.intersects(r);
         assert_eq!(r_thru_s.len(), 2);
         assert_eq!(r_thru_s[0].t, 5.);
@@ -117,7 +219,7 @@ mod sphere_tests {
     #[test]
     fn sphere_test_3() {
         let s = shapes::sphere_unit();
-        let r = ray(Tuple::point(0., 2., -5.), Tuple::vector(0., 0., 1.));
+        let r = ray(Point3::new(0., 2., -5.), Vector3::new(0., 0., 1.));
         let r_thru_s = s.intersects(r);
         assert_eq!(r_thru_s.len(), 0);
     }
@@ -125,7 +227,7 @@ mod sphere_tests {
     #[test]
     fn sphere_test_4() {
         let s = shapes::sphere_unit();
-        let r = ray(Tuple::point(0., 0., 0.), Tuple::vector(0., 0., 1.));
+        let r = ray(Point3::new(0., 0., 0.), Vector3::new(0., 0., 1.));
         let r_thru_s = s.intersects(r);
         assert_eq!(r_thru_s.len(), 2);
         assert_eq!(r_thru_s[0].t, -1.);
@@ -135,7 +237,7 @@ mod sphere_tests {
     #[test]
     fn sphere_test_5() {
         let s = shapes::sphere_unit();
-        let r = ray(Tuple::point(0., 0., 5.), Tuple::vector(0., 0., 1.));
+        let r = ray(Point3::new(0., 0., 5.), Vector3::new(0., 0., 1.));
         let r_thru_s = s.intersects(r);
         assert_eq!(r_thru_s.len(), 2);
         assert_eq!(r_thru_s[0].t, -6.);
@@ -177,17 +279,92 @@ mod sphere_tests {
     #[test]
     fn sphere_transform_intersect_test() {
         let s = shapes::sphere(scale(2., 2., 2.));
-        let r = ray(Tuple::point(0., 0., -5.), Tuple::vector(0., 0., 1.));
+        let r = ray(Point3::new(0., 0., -5.), Vector3::new(0., 0., 1.));
         let x = s.intersects(r);
         assert_eq!(x.len(), 2);
         assert_eq!(x[0].t, 3.);
         assert_eq!(x[1].t, 7.);
     }
 
+    #[test]
+    fn normal_test() {
+        let s = shapes::sphere_unit();
+        let n = s.normal_at(Point3::new(1., 0., 0.));
+        assert_eq!(n, Normal3::new(1., 0., 0.));
+        let k = (3. as Float).sqrt() / 3.;
+        let n = s.normal_at(Point3::new(k, k, k));
+        assert_eq!(n, Normal3::new(k, k, k));
+        assert!(n.eq(&n.unit()));
+
+        let s = shapes::sphere(translation(0., 1., 0.));
+        let k = FRAC_1_SQRT_2;
+        let n = s.normal_at(Point3::new(0., 1. + k, -k));
+        assert_eq!(n, Normal3::new(0., k, -k));
+
+        let s = shapes::sphere(scale(1., 0.5, 1.));
+        let n = s.normal_at(Point3::new(0., k, -k));
+        // (0, 4, -1) / sqrt(17), i.e. (0, 0.97014, -0.24254)
+        let r = (17. as Float).sqrt();
+        assert_eq!(n, Normal3::new(0., 4. / r, -1. / r));
+    }
+
+    #[test]
+    fn cached_inverse() {
+        let mut s = shapes::sphere(scale(2., 2., 2.));
+        assert_eq!(s.inverse(), scale(0.5, 0.5, 0.5));
+        s.set_transform(translation(0., 0., 1.))
+            .expect("invertible");
+        assert_eq!(s.inverse(), translation(0., 0., -1.));
+        let r = ray(Point3::new(0., 0., -5.), Vector3::new(0., 0., 1.));
+        let x = s.intersects(r);
+        assert_eq!((x[0].t, x[1].t), (5., 7.));
+        let n = s.normal_at(Point3::new(0., 1., 1.));
+        assert_eq!(n, Normal3::new(0., 1., 0.));
+        // the cached transpose follows the transform too
+        let squash = translation(1., 0., 0.) * scale(1., 0.5, 1.);
+        s.set_transform(squash).expect("invertible");
+        assert_eq!(s.normal_matrix, squash.inverse().transpose());
+        let p = Point3::new(1., 0.25, 0.5);
+        let object_normal = Normal3::from(squash.inverse() * p - Point3::ORIGIN);
+        let expected = squash.inverse().transform_normal(object_normal).unit();
+        assert_eq!(s.normal_at(p), expected);
+    }
+
+    #[test]
+    fn singular_transform() {
+        let flat = scale(1., 0., 1.);
+        let e = shapes::try_sphere(flat).err().expect("singular");
+        assert_eq!(*e.transform, flat);
+        let mut s = shapes::sphere(scale(2., 2., 2.));
+        assert!(s.set_transform(flat).is_err());
+        assert_eq!(s.transform(), scale(2., 2., 2.));
+        assert!(std::panic::catch_unwind(|| shapes::sphere(flat)).is_err());
+    }
+
+    #[test]
+    fn packet_hits() {
+        let rays = [
+            // from outside, from inside, from behind, and a miss
+            ray(Point3::new(2.3, 0.2, -5.), Vector3::new(0., 0., 1.)),
+            ray(Point3::new(2., 0.5, 0.), Vector3::new(0.6, 0.8, 0.)),
+            ray(Point3::new(0., 0., 5.), Vector3::new(0., 0., 1.)),
+            ray(Point3::new(0., 9., -5.), Vector3::new(0., 0., 1.)),
+        ];
+        let packet = RayPacket::new([&rays[0], &rays[1], &rays[2], &rays[3]]);
+        let s = shapes::sphere(translation(2., 0., 0.) * rotate_z(0.4) * scale(2., 1., 1.));
+        let hits = s.hit_packet(&packet);
+        assert!(hits[0].is_some() && hits[1].is_some());
+        assert_eq!(hits[2..], [None, None]);
+        for (r, packed) in rays.iter().zip(hits.iter()) {
+            let single = hit(s.intersects(r.clone())).map(|i| i.t);
+            assert_eq!(single.map(Float::to_bits), packed.map(Float::to_bits));
+        }
+    }
+
     #[test]
     fn sphere_transform_intersect_test_2() {
         let s = shapes::sphere(translation(5., 0., 0.));
-        let r = ray(Tuple::point(0., 0., -5.), Tuple::vector(0., 0., 1.));
+        let r = ray(Point3::new(0., 0., -5.), Vector3::new(0., 0., 1.));
         let x = s.intersects(r);
         assert_eq!(x.len(), 0);
     }
@@ -0,0 +1,295 @@
+//! Four lanes of `Float` for the small kernels that dominate rendering:
+//! tuple arithmetic, matrix products and ray packets. With the `simd`
+//! feature on x86_64 the lanes live in an SSE register; otherwise, and
+//! always with the `f64` feature, they are a plain array. Both backends
+//! round every lane exactly as the scalar code does. The kernels are a
+//! few instructions each and called from other modules, hence `#[inline]`.
+
+use crate::Float;
+use std::ops::{Add, Div, Mul, Sub};
+
+/// Whether this build uses the SSE backend. The `simd` feature alone is
+/// not enough: it also needs x86_64 and `f32`.
+pub const SSE: bool = cfg!(all(
+    feature = "simd",
+    target_arch = "x86_64",
+    not(feature = "f64")
+));
+
+#[cfg(all(feature = "simd", target_arch = "x86_64", not(feature = "f64")))]
+mod backend {
+    use std::arch::x86_64::*;
+
+    // SSE is part of the x86_64 baseline, so the intrinsics below are
+    // always available; the only store is into an aligned local array.
+    #[derive(Clone, Copy)]
+    pub struct Lanes(__m128);
+
+    #[repr(C, align(16))]
+    struct Aligned([f32; 4]);
+
+    // `_MM_SHUFFLE(3, 0, 2, 1)` and `_MM_SHUFFLE(3, 1, 0, 2)`
+    const YZXW: i32 = 0b11_00_10_01;
+    const ZXYW: i32 = 0b11_01_00_10;
+
+    impl Lanes {
+        #[inline]
+        pub fn new([x, y, z, w]: [f32; 4]) -> Lanes {
+            Lanes(unsafe { _mm_set_ps(w, z, y, x) })
+        }
+        #[inline]
+        pub fn splat(v: f32) -> Lanes {
+            Lanes(unsafe { _mm_set1_ps(v) })
+        }
+        #[inline]
+        pub fn to_array(self) -> [f32; 4] {
+            let mut out = Aligned([0.; 4]);
+            unsafe { _mm_store_ps(out.0.as_mut_ptr(), self.0) };
+            out.0
+        }
+        #[inline]
+        pub fn add(self, o: Lanes) -> Lanes {
+            Lanes(unsafe { _mm_add_ps(self.0, o.0) })
+        }
+        #[inline]
+        pub fn sub(self, o: Lanes) -> Lanes {
+            Lanes(unsafe { _mm_sub_ps(self.0, o.0) })
+        }
+        #[inline]
+        pub fn mul(self, o: Lanes) -> Lanes {
+            Lanes(unsafe { _mm_mul_ps(self.0, o.0) })
+        }
+        #[inline]
+        pub fn div(self, o: Lanes) -> Lanes {
+            Lanes(unsafe { _mm_div_ps(self.0, o.0) })
+        }
+        #[inline]
+        pub fn sqrt(self) -> Lanes {
+            Lanes(unsafe { _mm_sqrt_ps(self.0) })
+        }
+        #[inline]
+        pub fn yzxw(self) -> Lanes {
+            Lanes(unsafe { _mm_shuffle_ps::<YZXW>(self.0, self.0) })
+        }
+        #[inline]
+        pub fn zxyw(self) -> Lanes {
+            Lanes(unsafe { _mm_shuffle_ps::<ZXYW>(self.0, self.0) })
+        }
+    }
+}
+
+#[cfg(not(all(feature = "simd", target_arch = "x86_64", not(feature = "f64"))))]
+mod backend {
+    use crate::Float;
+
+    #[derive(Clone, Copy)]
+    pub struct Lanes([Float; 4]);
+
+    impl Lanes {
+        #[inline]
+        pub fn new(v: [Float; 4]) -> Lanes {
+            Lanes(v)
+        }
+        #[inline]
+        pub fn splat(v: Float) -> Lanes {
+            Lanes([v; 4])
+        }
+        #[inline]
+        pub fn to_array(self) -> [Float; 4] {
+            self.0
+        }
+        #[inline]
+        fn zip(self, o: Lanes, f: impl Fn(Float, Float) -> Float) -> Lanes {
+            let (a, b) = (self.0, o.0);
+            Lanes([f(a[0], b[0]), f(a[1], b[1]), f(a[2], b[2]), f(a[3], b[3])])
+        }
+        #[inline]
+        pub fn add(self, o: Lanes) -> Lanes {
+            self.zip(o, |a, b| a + b)
+        }
+        #[inline]
+        pub fn sub(self, o: Lanes) -> Lanes {
+            self.zip(o, |a, b| a - b)
+        }
+        #[inline]
+        pub fn mul(self, o: Lanes) -> Lanes {
+            self.zip(o, |a, b| a * b)
+        }
+        #[inline]
+        pub fn div(self, o: Lanes) -> Lanes {
+            self.zip(o, |a, b| a / b)
+        }
+        #[inline]
+        pub fn sqrt(self) -> Lanes {
+            Lanes(self.0.map(Float::sqrt))
+        }
+        #[inline]
+        pub fn yzxw(self) -> Lanes {
+            let [x, y, z, w] = self.0;
+            Lanes([y, z, x, w])
+        }
+        #[inline]
+        pub fn zxyw(self) -> Lanes {
+            let [x, y, z, w] = self.0;
+            Lanes([z, x, y, w])
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct Float4(backend::Lanes);
+
+impl Float4 {
+    #[inline]
+    pub fn new(x: Float, y: Float, z: Float, w: Float) -> Float4 {
+        Float4(backend::Lanes::new([x, y, z, w]))
+    }
+    #[inline]
+    pub fn splat(v: Float) -> Float4 {
+        Float4(backend::Lanes::splat(v))
+    }
+    #[inline]
+    pub fn to_array(self) -> [Float; 4] {
+        self.0.to_array()
+    }
+    #[inline]
+    pub fn sqrt(self) -> Float4 {
+        Float4(self.0.sqrt())
+    }
+    /// Lane products summed in lane order, as the scalar dot product does.
+    #[inline]
+    pub fn dot(self, o: Float4) -> Float {
+        let [x, y, z, w] = (self * o).to_array();
+        x + y + z + w
+    }
+    /// `dot` over the first three lanes only.
+    #[inline]
+    pub fn dot3(self, o: Float4) -> Float {
+        let [x, y, z, _] = (self * o).to_array();
+        x + y + z
+    }
+    /// Cross product of the first three lanes; the last lane is zero.
+    #[inline]
+    pub fn cross(self, o: Float4) -> Float4 {
+        let c = self.yzxw() * o.zxyw() - self.zxyw() * o.yzxw();
+        let [x, y, z, _] = c.to_array();
+        Float4::new(x, y, z, 0.)
+    }
+    #[inline]
+    fn yzxw(self) -> Float4 {
+        Float4(self.0.yzxw())
+    }
+    #[inline]
+    fn zxyw(self) -> Float4 {
+        Float4(self.0.zxyw())
+    }
+}
+
+impl Add for Float4 {
+    type Output = Float4;
+    #[inline]
+    fn add(self, o: Float4) -> Float4 {
+        Float4(self.0.add(o.0))
+    }
+}
+
+impl Sub for Float4 {
+    type Output = Float4;
+    #[inline]
+    fn sub(self, o: Float4) -> Float4 {
+        Float4(self.0.sub(o.0))
+    }
+}
+
+impl Mul for Float4 {
+    type Output = Float4;
+    #[inline]
+    fn mul(self, o: Float4) -> Float4 {
+        Float4(self.0.mul(o.0))
+    }
+}
+
+impl Div for Float4 {
+    type Output = Float4;
+    #[inline]
+    fn div(self, o: Float4) -> Float4 {
+        Float4(self.0.div(o.0))
+    }
+}
+
+fn row(r: [Float; 4]) -> Float4 {
+    Float4::new(r[0], r[1], r[2], r[3])
+}
+
+/// Row-major `a * b`: each output row is a sum of the rows of `b`.
+pub fn mm(a: [[Float; 4]; 4], b: [[Float; 4]; 4]) -> [[Float; 4]; 4] {
+    let b = [row(b[0]), row(b[1]), row(b[2]), row(b[3])];
+    a.map(|r| {
+        (b[0] * Float4::splat(r[0])
+            + b[1] * Float4::splat(r[1])
+            + b[2] * Float4::splat(r[2])
+            + b[3] * Float4::splat(r[3]))
+        .to_array()
+    })
+}
+
+/// `m * v`, as a sum of the columns of `m`.
+pub fn transform(m: [[Float; 4]; 4], v: [Float; 4]) -> [Float; 4] {
+    let col = |j: usize| Float4::new(m[0][j], m[1][j], m[2][j], m[3][j]);
+    (col(0) * Float4::splat(v[0])
+        + col(1) * Float4::splat(v[1])
+        + col(2) * Float4::splat(v[2])
+        + col(3) * Float4::splat(v[3]))
+    .to_array()
+}
+
+#[cfg(test)]
+mod simd_tests {
+    use super::*;
+
+    #[test]
+    fn lanes() {
+        let a = Float4::new(1., 2., 3., 4.);
+        let b = Float4::new(2., 3., 4., 5.);
+        assert_eq!((a + b).to_array(), [3., 5., 7., 9.]);
+        assert_eq!((a - b).to_array(), [-1.; 4]);
+        assert_eq!((a * b).to_array(), [2., 6., 12., 20.]);
+        assert_eq!((b / Float4::splat(2.)).to_array(), [1., 1.5, 2., 2.5]);
+        assert_eq!(
+            Float4::new(4., 9., 0., 1.).sqrt().to_array(),
+            [2., 3., 0., 1.]
+        );
+        assert_eq!(a.dot(b), 40.);
+        assert_eq!(a.dot3(b), 20.);
+        assert_eq!(a.cross(b).to_array(), [-1., 2., -1., 0.]);
+        assert_eq!(b.cross(a).to_array(), [1., -2., 1., 0.]);
+    }
+
+    #[test]
+    fn matches_scalar() {
+        // same operations in the same order, so results are bit-identical
+        let a = [
+            [0.1, 2.7, -3.3, 4.],
+            [5.5, 0.6, 7.25, -8.],
+            [9., 8.125, 0.7, 6.],
+            [-5., 4.4, 3., 0.2],
+        ];
+        let b = [
+            [-2.1, 1., 2.3, 3.],
+            [3., 2.9, 1., -1.7],
+            [4.6, 3., 6.1, 5.],
+            [1., 2.2, 7., 8.3],
+        ];
+        let mut expected = [[0.; 4]; 4];
+        for i in 0..4 {
+            for j in 0..4 {
+                expected[i][j] =
+                    a[i][0] * b[0][j] + a[i][1] * b[1][j] + a[i][2] * b[2][j] + a[i][3] * b[3][j];
+            }
+        }
+        assert_eq!(mm(a, b), expected);
+        let v = [0.3, -1.9, 2.2, 1.];
+        let expected = a.map(|r| r[0] * v[0] + r[1] * v[1] + r[2] * v[2] + r[3] * v[3]);
+        assert_eq!(transform(a, v), expected);
+    }
+}
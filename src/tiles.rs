@@ -0,0 +1,179 @@
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TileOrder {
+    Scanline,
+    /// Outward from the center tile, so the interesting part shows up first.
+    Spiral,
+    /// Along a Hilbert curve, keeping consecutive tiles next to each other.
+    Hilbert,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Tile {
+    /// Position of this tile in the schedule.
+    pub index: usize,
+    pub x: u64,
+    pub y: u64,
+    pub width: u64,
+    pub height: u64,
+}
+
+fn spiral(cols: i64, rows: i64) -> Vec<(i64, i64)> {
+    let (mut x, mut y) = ((cols - 1) / 2, (rows - 1) / 2);
+    let total = (cols * rows) as usize;
+    let mut out = Vec::with_capacity(total);
+    let directions = [(1, 0), (0, 1), (-1, 0), (0, -1)];
+    let mut step = 1;
+    let mut d = 0;
+    while out.len() < total {
+        // each step length is walked twice: right+down, left+up, ...
+        for _ in 0..2 {
+            let (dx, dy) = directions[d % 4];
+            for _ in 0..step {
+                if x >= 0 && x < cols && y >= 0 && y < rows {
+                    out.push((x, y));
+                }
+                x += dx;
+                y += dy;
+            }
+            d += 1;
+        }
+        step += 1;
+    }
+    out.truncate(total);
+    out
+}
+
+/// Distance along the Hilbert curve filling an `n` x `n` grid, `n` a power of two.
+fn hilbert_index(n: u64, x: u64, y: u64) -> u64 {
+    let (mut x, mut y) = (x, y);
+    let mut d = 0;
+    let mut s = n / 2;
+    while s > 0 {
+        let rx = (x & s > 0) as u64;
+        let ry = (y & s > 0) as u64;
+        d += s * s * ((3 * rx) ^ ry);
+        if ry == 0 {
+            if rx == 1 {
+                x = s - 1 - (x & (s - 1));
+                y = s - 1 - (y & (s - 1));
+            } else {
+                x &= s - 1;
+                y &= s - 1;
+            }
+            std::mem::swap(&mut x, &mut y);
+        } else {
+            x &= s - 1;
+            y &= s - 1;
+        }
+        s /= 2;
+    }
+    d
+}
+
+/// Splits a `width` x `height` image into tiles of at most `size` x `size`
+/// pixels, listed in the order they should be rendered.
+pub fn tiles(width: u64, height: u64, size: u64, order: TileOrder) -> Vec<Tile> {
+    let size = size.max(1);
+    let cols = width.div_ceil(size);
+    let rows = height.div_ceil(size);
+    let cells: Vec<(u64, u64)> = match order {
+        TileOrder::Scanline => (0..rows)
+            .flat_map(|r| (0..cols).map(move |c| (c, r)))
+            .collect(),
+        TileOrder::Spiral => spiral(cols as i64, rows as i64)
+            .into_iter()
+            .map(|(c, r)| (c as u64, r as u64))
+            .collect(),
+        TileOrder::Hilbert => {
+            let n = cols.max(rows).next_power_of_two();
+            let mut cells: Vec<(u64, u64)> = (0..rows)
+                .flat_map(|r| (0..cols).map(move |c| (c, r)))
+                .collect();
+            cells.sort_by_key(|&(c, r)| hilbert_index(n, c, r));
+            cells
+        }
+    };
+    cells
+        .into_iter()
+        .enumerate()
+        .map(|(index, (c, r))| Tile {
+            index,
+            x: c * size,
+            y: r * size,
+            width: size.min(width - c * size),
+            height: size.min(height - r * size),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tile_tests {
+    use super::*;
+
+    fn covers_exactly_once(width: u64, height: u64, ts: &[Tile]) {
+        let mut seen = vec![0; (width * height) as usize];
+        for t in ts {
+            for y in t.y..t.y + t.height {
+                for x in t.x..t.x + t.width {
+                    seen[(y * width + x) as usize] += 1;
+                }
+            }
+        }
+        assert!(seen.iter().all(|&n| n == 1));
+    }
+
+    #[test]
+    fn scanline() {
+        let ts = tiles(10, 5, 4, TileOrder::Scanline);
+        assert_eq!(ts.len(), 6);
+        assert_eq!(
+            ts[2],
+            Tile {
+                index: 2,
+                x: 8,
+                y: 0,
+                width: 2,
+                height: 4
+            }
+        );
+        assert_eq!((ts[3].x, ts[3].y, ts[3].height), (0, 4, 1));
+        covers_exactly_once(10, 5, &ts);
+    }
+
+    #[test]
+    fn spiral_order() {
+        let ts = tiles(30, 30, 10, TileOrder::Spiral);
+        let cells: Vec<(u64, u64)> = ts.iter().map(|t| (t.x / 10, t.y / 10)).collect();
+        assert_eq!(
+            cells,
+            vec![
+                (1, 1),
+                (2, 1),
+                (2, 2),
+                (1, 2),
+                (0, 2),
+                (0, 1),
+                (0, 0),
+                (1, 0),
+                (2, 0)
+            ]
+        );
+        covers_exactly_once(37, 11, &tiles(37, 11, 4, TileOrder::Spiral));
+    }
+
+    #[test]
+    fn hilbert_order() {
+        let ts = tiles(8, 8, 4, TileOrder::Hilbert);
+        let cells: Vec<(u64, u64)> = ts.iter().map(|t| (t.x / 4, t.y / 4)).collect();
+        assert_eq!(cells, vec![(0, 0), (0, 1), (1, 1), (1, 0)]);
+        let ts = tiles(50, 30, 4, TileOrder::Hilbert);
+        covers_exactly_once(50, 30, &ts);
+        // consecutive tiles on a full curve are always neighbours
+        let ts = tiles(32, 32, 4, TileOrder::Hilbert);
+        for w in ts.windows(2) {
+            let dx = (w[0].x as i64 - w[1].x as i64).abs();
+            let dy = (w[0].y as i64 - w[1].y as i64).abs();
+            assert_eq!(dx + dy, 4);
+        }
+    }
+}
@@ -0,0 +1,191 @@
+use crate::matrix::matrix::Matrix4;
+use crate::shapes::SingularTransform;
+
+/// A transform built up step by step, with its inverse kept alongside so
+/// it never has to be inverted from scratch, and a stack of saved states
+/// for walking a hierarchy.
+///
+/// Steps added with `then` (or `pre_multiply`) apply after everything so
+/// far, in the order written, like `chain`. Steps added with
+/// `post_multiply` apply before everything so far, which is how a child's
+/// local transform combines with its parent's.
+#[derive(Debug, Clone)]
+pub struct TransformStack {
+    matrix: Matrix4,
+    inverse: Matrix4,
+    saved: Vec<(Matrix4, Matrix4)>,
+}
+
+impl Default for TransformStack {
+    fn default() -> TransformStack {
+        TransformStack {
+            matrix: Matrix4::IDENTITY,
+            inverse: Matrix4::IDENTITY,
+            saved: Vec::new(),
+        }
+    }
+}
+
+pub fn transform_stack() -> TransformStack {
+    TransformStack::default()
+}
+
+impl TransformStack {
+    pub fn matrix(&self) -> Matrix4 {
+        self.matrix
+    }
+    pub fn inverse(&self) -> Matrix4 {
+        self.inverse
+    }
+    /// Number of saved states.
+    pub fn depth(&self) -> usize {
+        self.saved.len()
+    }
+    /// Saves the current transform, to be restored by `pop`.
+    pub fn push(&mut self) -> &mut TransformStack {
+        self.saved.push((self.matrix, self.inverse));
+        self
+    }
+    /// Restores the last saved transform and returns the one it replaces,
+    /// or `None`, changing nothing, if nothing is saved.
+    pub fn pop(&mut self) -> Option<Matrix4> {
+        let (matrix, inverse) = self.saved.pop()?;
+        let popped = self.matrix;
+        self.matrix = matrix;
+        self.inverse = inverse;
+        Some(popped)
+    }
+    /// Applies `m` after the current transform: it becomes `m * current`.
+    /// Leaves the stack unchanged if `m` is singular.
+    pub fn try_pre_multiply(&mut self, m: Matrix4) -> Result<(), SingularTransform> {
+        let inverse = invert(m)?;
+        self.matrix = m * self.matrix;
+        self.inverse = self.inverse * inverse;
+        Ok(())
+    }
+    /// Applies `m` before the current transform: it becomes
+    /// `current * m`. Leaves the stack unchanged if `m` is singular.
+    pub fn try_post_multiply(&mut self, m: Matrix4) -> Result<(), SingularTransform> {
+        let inverse = invert(m)?;
+        self.matrix = self.matrix * m;
+        self.inverse = inverse * self.inverse;
+        Ok(())
+    }
+    /// Panics if `m` is singular; see `try_pre_multiply`.
+    pub fn pre_multiply(&mut self, m: Matrix4) -> &mut TransformStack {
+        if let Err(e) = self.try_pre_multiply(m) {
+            panic!("{}", e);
+        }
+        self
+    }
+    /// Panics if `m` is singular; see `try_post_multiply`.
+    pub fn post_multiply(&mut self, m: Matrix4) -> &mut TransformStack {
+        if let Err(e) = self.try_post_multiply(m) {
+            panic!("{}", e);
+        }
+        self
+    }
+    /// `pre_multiply`, named for reading steps in the order they apply.
+    pub fn then(&mut self, m: Matrix4) -> &mut TransformStack {
+        self.pre_multiply(m)
+    }
+}
+
+fn invert(m: Matrix4) -> Result<Matrix4, SingularTransform> {
+    m.try_inverse().ok_or(SingularTransform {
+        transform: Box::new(m),
+    })
+}
+
+#[cfg(test)]
+mod transform_tests {
+    use super::*;
+    use crate::consts::PI;
+    use crate::matrix::matrix::{chain, rotate_x, rotate_y, scale, shear, translation};
+    use crate::tuple::Point3;
+
+    #[test]
+    fn then_matches_chain() {
+        let (a, b, c) = (
+            rotate_x(PI / 2.),
+            scale(5., 5., 5.),
+            translation(10., 5., 7.),
+        );
+        let mut t = transform_stack();
+        t.then(a).then(b).then(c);
+        assert_eq!(t.matrix(), chain(vec![a, b, c]));
+        assert_eq!(t.matrix(), c * b * a);
+        assert_eq!(
+            t.matrix() * Point3::new(1., 0., 1.),
+            Point3::new(15., 0., 7.)
+        );
+        assert_eq!(t.inverse(), t.matrix().inverse());
+        assert_eq!(t.matrix() * t.inverse(), Matrix4::IDENTITY);
+
+        // chain's edge cases
+        assert_eq!(chain(vec![]), Matrix4::IDENTITY);
+        assert_eq!(chain(vec![b]), b);
+        assert_eq!(transform_stack().matrix(), Matrix4::IDENTITY);
+    }
+
+    #[test]
+    fn pre_and_post() {
+        let (a, b) = (translation(1., 2., 3.), rotate_y(0.8));
+        let mut t = transform_stack();
+        t.pre_multiply(a).post_multiply(b);
+        // b was added last but applies first
+        assert_eq!(t.matrix(), a * b);
+        assert_eq!(t.inverse(), (a * b).inverse());
+        let sheared = shear(0.5, 0., 0., 0.2, 0., 0.);
+        t.pre_multiply(sheared);
+        assert_eq!(t.matrix(), sheared * a * b);
+        assert_eq!(t.inverse() * t.matrix(), Matrix4::IDENTITY);
+    }
+
+    #[test]
+    fn push_pop() {
+        // a parent at (0, 2, 0) with two children placed relative to it
+        let mut t = transform_stack();
+        t.then(translation(0., 2., 0.));
+        assert_eq!(t.pop(), None);
+        assert_eq!(t.matrix(), translation(0., 2., 0.));
+
+        t.push().post_multiply(translation(1., 0., 0.));
+        assert_eq!(t.depth(), 1);
+        assert_eq!(t.matrix() * Point3::ORIGIN, Point3::new(1., 2., 0.));
+        assert_eq!(t.pop(), Some(translation(1., 2., 0.)));
+
+        t.push().post_multiply(scale(2., 2., 2.));
+        t.push().post_multiply(translation(0., 1., 0.));
+        assert_eq!(t.matrix() * Point3::ORIGIN, Point3::new(0., 4., 0.));
+        assert_eq!(t.inverse() * Point3::new(0., 4., 0.), Point3::ORIGIN);
+        t.pop();
+        t.pop();
+        assert_eq!(t.depth(), 0);
+        assert_eq!(t.matrix(), translation(0., 2., 0.));
+        assert_eq!(t.inverse(), translation(0., -2., 0.));
+    }
+
+    #[test]
+    fn singular() {
+        let mut t = transform_stack();
+        t.then(scale(2., 2., 2.));
+        let flat = scale(1., 0., 1.);
+        let e = t.try_pre_multiply(flat).expect_err("singular");
+        assert_eq!(*e.transform, flat);
+        assert!(t.try_post_multiply(flat).is_err());
+        assert_eq!(t.matrix(), scale(2., 2., 2.));
+        assert!(std::panic::catch_unwind(|| transform_stack().then(flat).depth()).is_err());
+
+        // a large translation is far from singular
+        let far = translation(1e7, 0., 0.);
+        assert!(t.try_pre_multiply(far).is_ok());
+        t.then(scale(0.1, 0.1, 0.1)).post_multiply(far);
+        let origin = t.matrix() * Point3::ORIGIN;
+        assert_eq!(t.inverse() * origin, Point3::ORIGIN);
+        assert_eq!(
+            transform_stack().then(far).inverse(),
+            translation(-1e7, 0., 0.)
+        );
+    }
+}
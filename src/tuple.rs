@@ -1,11 +1,13 @@
-use self::super::equals;
+use self::super::{equals, Float};
+use crate::simd::Float4;
+use std::ops::{Add, Div, Mul, Neg, Sub};
 
 #[derive(Default, Clone)]
 pub struct Tuple {
-    pub x: f32,
-    pub y: f32,
-    pub z: f32,
-    pub w: f32,
+    pub x: Float,
+    pub y: Float,
+    pub z: Float,
+    pub w: Float,
 }
 
 impl PartialEq for Tuple {
@@ -24,10 +26,10 @@ impl Tuple {
     pub fn is_vector(&self) -> bool {
         equals(self.w, 0.0)
     }
-    pub fn vector(x: f32, y: f32, z: f32) -> Tuple {
+    pub const fn vector(x: Float, y: Float, z: Float) -> Tuple {
         Tuple { x, y, z, w: 0.0 }
     }
-    pub fn point(x: f32, y: f32, z: f32) -> Tuple {
+    pub const fn point(x: Float, y: Float, z: Float) -> Tuple {
         Tuple { x, y, z, w: 1.0 }
     }
     pub fn as_vector(&self) -> Tuple {
@@ -38,21 +40,18 @@ impl Tuple {
             w: 0.,
         }
     }
+    fn lanes(&self) -> Float4 {
+        Float4::new(self.x, self.y, self.z, self.w)
+    }
+    fn from_lanes(v: Float4) -> Tuple {
+        let [x, y, z, w] = v.to_array();
+        Tuple { x, y, z, w }
+    }
     pub fn add(&self, other: Tuple) -> Tuple {
-        Tuple {
-            x: self.x + other.x,
-            y: self.y + other.y,
-            z: self.z + other.z,
-            w: self.w + other.w,
-        }
+        Tuple::from_lanes(self.lanes() + other.lanes())
     }
     pub fn subtract(&self, other: Tuple) -> Tuple {
-        Tuple {
-            x: self.x - other.x,
-            y: self.y - other.y,
-            z: self.z - other.z,
-            w: self.w - other.w,
-        }
+        Tuple::from_lanes(self.lanes() - other.lanes())
     }
     pub fn negate(&self) -> Tuple {
         Tuple {
@@ -62,15 +61,10 @@ impl Tuple {
             w: -self.w,
         }
     }
-    pub fn multiply(&self, k: f32) -> Tuple {
-        Tuple {
-            x: k * self.x,
-            y: k * self.y,
-            z: k * self.z,
-            w: k * self.w,
-        }
+    pub fn multiply(&self, k: Float) -> Tuple {
+        Tuple::from_lanes(Float4::splat(k) * self.lanes())
     }
-    pub fn divide(&self, k: f32) -> Tuple {
+    pub fn divide(&self, k: Float) -> Tuple {
         Tuple {
             x: self.x / k,
             y: self.y / k,
@@ -78,7 +72,7 @@ impl Tuple {
             w: self.w / k,
         }
     }
-    pub fn norm(&self) -> f32 {
+    pub fn norm(&self) -> Float {
         (self.x * self.x + self.y * self.y + self.z * self.z + self.w * self.w).sqrt()
     }
     pub fn unit(&self) -> Tuple {
@@ -90,21 +84,215 @@ impl Tuple {
             w: self.w / n,
         }
     }
-    pub fn dot(&self, other: Tuple) -> f32 {
-        self.x * other.x + self.y * other.y + self.z * other.z + self.w * other.w
+    pub fn dot(&self, other: Tuple) -> Float {
+        self.lanes().dot(other.lanes())
     }
     pub fn cross(&self, other: Tuple) -> Tuple {
-        Tuple::vector(
-            self.y * other.z - self.z * other.y,
-            self.z * other.x - self.x * other.z,
-            self.x * other.y - self.y * other.x,
-        )
+        Tuple::from_lanes(self.lanes().cross(other.lanes()))
+    }
+}
+
+/// A position in space. Subtracting two points gives the vector between
+/// them; points can be moved by vectors but not added to each other.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Point3 {
+    pub x: Float,
+    pub y: Float,
+    pub z: Float,
+}
+
+/// A direction and length. Unlike points, vectors ignore translation.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Vector3 {
+    pub x: Float,
+    pub y: Float,
+    pub z: Float,
+}
+
+/// A surface normal. Normals transform by the inverse transpose, so they
+/// have their own type; see `Matrix4::transform_normal`.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Normal3 {
+    pub x: Float,
+    pub y: Float,
+    pub z: Float,
+}
+
+impl Point3 {
+    pub const ORIGIN: Point3 = Point3::new(0., 0., 0.);
+
+    pub const fn new(x: Float, y: Float, z: Float) -> Point3 {
+        Point3 { x, y, z }
+    }
+    fn lanes(self) -> Float4 {
+        Float4::new(self.x, self.y, self.z, 1.)
+    }
+    fn from_lanes(v: Float4) -> Point3 {
+        let [x, y, z, _] = v.to_array();
+        Point3::new(x, y, z)
+    }
+}
+
+impl Vector3 {
+    pub const fn new(x: Float, y: Float, z: Float) -> Vector3 {
+        Vector3 { x, y, z }
+    }
+    pub fn dot(self, other: Vector3) -> Float {
+        self.lanes().dot3(other.lanes())
+    }
+    pub fn cross(self, other: Vector3) -> Vector3 {
+        Vector3::from_lanes(self.lanes().cross(other.lanes()))
+    }
+    pub fn norm(self) -> Float {
+        self.dot(self).sqrt()
+    }
+    pub fn unit(self) -> Vector3 {
+        self / self.norm()
+    }
+    fn lanes(self) -> Float4 {
+        Float4::new(self.x, self.y, self.z, 0.)
+    }
+    fn from_lanes(v: Float4) -> Vector3 {
+        let [x, y, z, _] = v.to_array();
+        Vector3::new(x, y, z)
+    }
+}
+
+impl Normal3 {
+    pub const fn new(x: Float, y: Float, z: Float) -> Normal3 {
+        Normal3 { x, y, z }
+    }
+    pub fn dot(self, v: Vector3) -> Float {
+        Vector3::from(self).dot(v)
+    }
+    pub fn unit(self) -> Normal3 {
+        Normal3::from(Vector3::from(self).unit())
+    }
+}
+
+impl PartialEq for Point3 {
+    fn eq(&self, o: &Point3) -> bool {
+        equals(self.x, o.x) && equals(self.y, o.y) && equals(self.z, o.z)
+    }
+}
+
+impl PartialEq for Vector3 {
+    fn eq(&self, o: &Vector3) -> bool {
+        equals(self.x, o.x) && equals(self.y, o.y) && equals(self.z, o.z)
+    }
+}
+
+impl PartialEq for Normal3 {
+    fn eq(&self, o: &Normal3) -> bool {
+        equals(self.x, o.x) && equals(self.y, o.y) && equals(self.z, o.z)
+    }
+}
+
+impl From<Normal3> for Vector3 {
+    fn from(n: Normal3) -> Vector3 {
+        Vector3::new(n.x, n.y, n.z)
+    }
+}
+
+impl From<Vector3> for Normal3 {
+    fn from(v: Vector3) -> Normal3 {
+        Normal3::new(v.x, v.y, v.z)
+    }
+}
+
+impl From<Point3> for Tuple {
+    fn from(p: Point3) -> Tuple {
+        Tuple::point(p.x, p.y, p.z)
+    }
+}
+
+impl From<Vector3> for Tuple {
+    fn from(v: Vector3) -> Tuple {
+        Tuple::vector(v.x, v.y, v.z)
+    }
+}
+
+impl Sub for Point3 {
+    type Output = Vector3;
+    fn sub(self, o: Point3) -> Vector3 {
+        Vector3::from_lanes(self.lanes() - o.lanes())
+    }
+}
+
+impl Add<Vector3> for Point3 {
+    type Output = Point3;
+    fn add(self, v: Vector3) -> Point3 {
+        Point3::from_lanes(self.lanes() + v.lanes())
+    }
+}
+
+impl Sub<Vector3> for Point3 {
+    type Output = Point3;
+    fn sub(self, v: Vector3) -> Point3 {
+        Point3::from_lanes(self.lanes() - v.lanes())
+    }
+}
+
+impl Add for Vector3 {
+    type Output = Vector3;
+    fn add(self, o: Vector3) -> Vector3 {
+        Vector3::from_lanes(self.lanes() + o.lanes())
+    }
+}
+
+impl Sub for Vector3 {
+    type Output = Vector3;
+    fn sub(self, o: Vector3) -> Vector3 {
+        Vector3::from_lanes(self.lanes() - o.lanes())
+    }
+}
+
+impl Mul<Float> for Vector3 {
+    type Output = Vector3;
+    fn mul(self, k: Float) -> Vector3 {
+        Vector3::from_lanes(self.lanes() * Float4::splat(k))
+    }
+}
+
+impl Mul<Vector3> for Float {
+    type Output = Vector3;
+    fn mul(self, v: Vector3) -> Vector3 {
+        v * self
+    }
+}
+
+impl Div<Float> for Vector3 {
+    type Output = Vector3;
+    fn div(self, k: Float) -> Vector3 {
+        Vector3::from_lanes(self.lanes() / Float4::splat(k))
+    }
+}
+
+impl Neg for Vector3 {
+    type Output = Vector3;
+    fn neg(self) -> Vector3 {
+        Vector3::new(-self.x, -self.y, -self.z)
+    }
+}
+
+impl Neg for Normal3 {
+    type Output = Normal3;
+    fn neg(self) -> Normal3 {
+        Normal3::new(-self.x, -self.y, -self.z)
+    }
+}
+
+/// Scaling a normal gives a plain vector, e.g. for reflection.
+impl Mul<Float> for Normal3 {
+    type Output = Vector3;
+    fn mul(self, k: Float) -> Vector3 {
+        Vector3::from(self) * k
     }
 }
 
 #[cfg(test)]
 mod tuple_tests {
-    use super::{equals, Tuple};
+    use super::{equals, Float, Normal3, Point3, Tuple, Vector3};
 
     #[test]
     fn subtract_vec() {
@@ -169,7 +357,9 @@ mod tuple_tests {
     #[test]
     fn unit() {
         let v = Tuple::vector(1.0, 2.0, 3.0);
-        let u = Tuple::vector(0.26726, 0.53452, 0.80178);
+        // (0.26726, 0.53452, 0.80178)
+        let r = (14. as Float).sqrt();
+        let u = Tuple::vector(1. / r, 2. / r, 3. / r);
         assert!(v.unit() == u);
     }
 
@@ -188,4 +378,31 @@ mod tuple_tests {
         assert!(a.cross(b.clone()) == c);
         assert!(b.cross(a) == c.multiply(-1.0))
     }
+
+    #[test]
+    fn typed() {
+        let p = Point3::new(3., 2., 1.);
+        let q = Point3::new(5., 6., 7.);
+        assert_eq!(p - q, Vector3::new(-2., -4., -6.));
+        let v = Vector3::new(5., 6., 7.);
+        assert_eq!(p - v, Point3::new(-2., -4., -6.));
+        assert_eq!(p + v, Point3::new(8., 8., 8.));
+        assert_eq!(v - v, Vector3::default());
+        assert_eq!(-v, Vector3::new(-5., -6., -7.));
+        assert_eq!(v * 2., 2. * v);
+        assert_eq!(v / 2., Vector3::new(2.5, 3., 3.5));
+        assert!(equals(Vector3::new(3., 0., -4.).norm(), 5.));
+        let a = Vector3::new(1., 2., 3.);
+        let b = Vector3::new(2., 3., 4.);
+        assert!(equals(a.dot(b), 20.));
+        assert_eq!(a.cross(b), Vector3::new(-1., 2., -1.));
+        assert_eq!(a.unit(), a / (14. as Float).sqrt());
+        let n = Normal3::new(0., 2., 0.);
+        assert_eq!(n.unit(), Normal3::new(0., 1., 0.));
+        assert_eq!(-n, Normal3::new(0., -2., 0.));
+        assert_eq!(n * 0.5, Vector3::new(0., 1., 0.));
+        assert!(equals(n.dot(a), 4.));
+        assert!(Tuple::from(p) == Tuple::point(3., 2., 1.));
+        assert!(Tuple::from(v).is_vector());
+    }
 }
@@ -0,0 +1,508 @@
+//! The subset of YAML used by scene files: block mappings and sequences
+//! nested by indentation, flow sequences like `[1, 2, 3]`, plain or quoted
+//! scalars and `#` comments. Anchors, block strings and flow mappings are
+//! not supported.
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Scalar(String),
+    List(Vec<Node>),
+    Map(Vec<(String, Node)>),
+}
+
+/// A value and the line it starts on, counting from 1.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Node {
+    pub line: usize,
+    pub value: Value,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Error {
+    pub line: usize,
+    pub message: String,
+}
+
+struct Line {
+    number: usize,
+    indent: usize,
+    text: String,
+}
+
+fn error<T>(line: usize, message: String) -> Result<T, Error> {
+    Err(Error { line, message })
+}
+
+fn strip_comment(text: &str) -> &str {
+    let mut quote = None;
+    let mut prev = ' ';
+    for (i, c) in text.char_indices() {
+        match quote {
+            Some(q) if c == q => quote = None,
+            Some(_) => {}
+            None if c == '"' || c == '\'' => quote = Some(c),
+            None if c == '#' && prev.is_whitespace() => return &text[..i],
+            None => {}
+        }
+        prev = c;
+    }
+    text
+}
+
+fn is_item(text: &str) -> bool {
+    text == "-" || text.starts_with("- ")
+}
+
+/// Splits `key: value` or `key:`; the value may be empty.
+fn split_key(text: &str) -> Option<(&str, &str)> {
+    if text.starts_with('[') || text.starts_with('"') || text.starts_with('\'') {
+        return None;
+    }
+    let (key, rest) = match text.find(": ") {
+        Some(i) => (&text[..i], &text[i + 2..]),
+        None => (text.strip_suffix(':')?, ""),
+    };
+    let key = key.trim();
+    if key.is_empty() {
+        None
+    } else {
+        Some((key, rest.trim()))
+    }
+}
+
+fn unquote(text: &str) -> &str {
+    for q in ['"', '\''].iter() {
+        if text.len() >= 2 && text.starts_with(*q) && text.ends_with(*q) {
+            return &text[1..text.len() - 1];
+        }
+    }
+    text
+}
+
+struct Flow<'a> {
+    text: &'a str,
+    pos: usize,
+    line: usize,
+}
+
+impl Flow<'_> {
+    fn skip_space(&mut self) {
+        let rest = &self.text[self.pos..];
+        self.pos += rest.len() - rest.trim_start().len();
+    }
+    fn peek(&self) -> Option<char> {
+        self.text[self.pos..].chars().next()
+    }
+    fn value(&mut self) -> Result<Node, Error> {
+        self.skip_space();
+        if self.peek() == Some('[') {
+            return self.list();
+        }
+        let start = self.pos;
+        let mut quote = None;
+        for (i, c) in self.text[start..].char_indices() {
+            match quote {
+                Some(q) if c == q => quote = None,
+                Some(_) => {}
+                None if c == '"' || c == '\'' => quote = Some(c),
+                None if c == ',' || c == ']' || c == '[' => {
+                    self.pos = start + i;
+                    break;
+                }
+                None => {}
+            }
+            self.pos = start + i + c.len_utf8();
+        }
+        let text = self.text[start..self.pos].trim();
+        if text.is_empty() {
+            return error(self.line, String::from("empty item in list"));
+        }
+        Ok(Node {
+            line: self.line,
+            value: Value::Scalar(unquote(text).to_string()),
+        })
+    }
+    fn list(&mut self) -> Result<Node, Error> {
+        self.pos += 1;
+        let mut items = vec![];
+        self.skip_space();
+        if self.peek() == Some(']') {
+            self.pos += 1;
+        } else {
+            loop {
+                items.push(self.value()?);
+                self.skip_space();
+                match self.peek() {
+                    Some(',') => self.pos += 1,
+                    Some(']') => {
+                        self.pos += 1;
+                        break;
+                    }
+                    Some(c) => return error(self.line, format!("unexpected `{}` in list", c)),
+                    None => return error(self.line, String::from("unclosed `[`")),
+                }
+            }
+        }
+        Ok(Node {
+            line: self.line,
+            value: Value::List(items),
+        })
+    }
+}
+
+/// An inline value: a scalar or a flow sequence.
+fn flow(text: &str, line: usize) -> Result<Node, Error> {
+    let mut f = Flow { text, pos: 0, line };
+    let node = f.value()?;
+    f.skip_space();
+    if f.pos < text.len() {
+        return error(line, format!("unexpected `{}`", &text[f.pos..]));
+    }
+    Ok(node)
+}
+
+struct Parser {
+    lines: Vec<Line>,
+    pos: usize,
+}
+
+impl Parser {
+    fn block(&mut self, indent: usize) -> Result<Node, Error> {
+        let line = &self.lines[self.pos];
+        if is_item(&line.text) {
+            self.list(indent)
+        } else if split_key(&line.text).is_some() {
+            self.map(indent)
+        } else {
+            self.pos += 1;
+            flow(
+                &self.lines[self.pos - 1].text,
+                self.lines[self.pos - 1].number,
+            )
+        }
+    }
+    fn next_indent(&self) -> Option<usize> {
+        self.lines.get(self.pos).map(|l| l.indent)
+    }
+    fn unexpected_indent(&self, indent: usize) -> Result<(), Error> {
+        match self.lines.get(self.pos) {
+            Some(l) if l.indent > indent => error(l.number, String::from("unexpected indentation")),
+            _ => Ok(()),
+        }
+    }
+    fn list(&mut self, indent: usize) -> Result<Node, Error> {
+        let first = self.lines[self.pos].number;
+        let mut items = vec![];
+        while self.next_indent() == Some(indent) && is_item(&self.lines[self.pos].text) {
+            let line = &mut self.lines[self.pos];
+            let rest = line.text[1..].trim_start().to_string();
+            if rest.is_empty() {
+                let number = line.number;
+                self.pos += 1;
+                match self.next_indent() {
+                    Some(i) if i > indent => items.push(self.block(i)?),
+                    _ => return error(number, String::from("missing value after `-`")),
+                }
+            } else {
+                // treat the rest of the line as the first line of a block
+                // indented to where it starts
+                let inner = indent + line.text.len() - rest.len();
+                line.indent = inner;
+                line.text = rest;
+                items.push(self.block(inner)?);
+            }
+        }
+        self.unexpected_indent(indent)?;
+        Ok(Node {
+            line: first,
+            value: Value::List(items),
+        })
+    }
+    fn map(&mut self, indent: usize) -> Result<Node, Error> {
+        let first = self.lines[self.pos].number;
+        let mut entries: Vec<(String, Node)> = vec![];
+        while self.next_indent() == Some(indent) && !is_item(&self.lines[self.pos].text) {
+            let line = &self.lines[self.pos];
+            let number = line.number;
+            let (key, rest) = match split_key(&line.text) {
+                Some((k, r)) => (k.to_string(), r.to_string()),
+                None => return error(number, String::from("expected `key: value`")),
+            };
+            if entries.iter().any(|(k, _)| *k == key) {
+                return error(number, format!("duplicate key `{}`", key));
+            }
+            self.pos += 1;
+            let value = if !rest.is_empty() {
+                flow(&rest, number)?
+            } else {
+                match self.next_indent() {
+                    Some(i) if i > indent => self.block(i)?,
+                    Some(i) if i == indent && is_item(&self.lines[self.pos].text) => {
+                        self.list(indent)?
+                    }
+                    _ => return error(number, format!("missing value for `{}`", key)),
+                }
+            };
+            entries.push((key, value));
+        }
+        self.unexpected_indent(indent)?;
+        Ok(Node {
+            line: first,
+            value: Value::Map(entries),
+        })
+    }
+}
+
+/// Parses a whole document. An empty document is an empty list.
+pub fn parse(source: &str) -> Result<Node, Error> {
+    let mut lines = vec![];
+    for (i, raw) in source.lines().enumerate() {
+        let text = strip_comment(raw).trim_end();
+        let content = text.trim_start();
+        if content.is_empty() || content == "---" {
+            continue;
+        }
+        let indent = &text[..text.len() - content.len()];
+        if indent.contains('\t') {
+            return error(i + 1, String::from("tabs are not allowed in indentation"));
+        }
+        lines.push(Line {
+            number: i + 1,
+            indent: indent.len(),
+            text: content.to_string(),
+        });
+    }
+    if lines.is_empty() {
+        return Ok(Node {
+            line: 1,
+            value: Value::List(vec![]),
+        });
+    }
+    let indent = lines[0].indent;
+    let mut p = Parser { lines, pos: 0 };
+    let node = p.block(indent)?;
+    if let Some(l) = p.lines.get(p.pos) {
+        return error(l.number, String::from("unexpected indentation"));
+    }
+    Ok(node)
+}
+
+fn is_flat(items: &[Node]) -> bool {
+    items.iter().all(|n| matches!(n.value, Value::Scalar(_)))
+}
+
+fn inline(node: &Node) -> Option<String> {
+    match &node.value {
+        Value::Scalar(s) => {
+            let plain = !s.is_empty()
+                && s.trim() == s
+                && split_key(s).is_none()
+                && !is_item(s)
+                && !s.starts_with(['"', '\'', '['])
+                && !s.contains([',', '[', ']', '#']);
+            Some(if plain {
+                s.clone()
+            } else {
+                format!("\"{}\"", s)
+            })
+        }
+        Value::List(items) if is_flat(items) => {
+            let items: Vec<String> = items.iter().filter_map(inline).collect();
+            Some(format!("[{}]", items.join(", ")))
+        }
+        _ => None,
+    }
+}
+
+fn write_block(node: &Node, indent: usize, out: &mut String) {
+    let pad = " ".repeat(indent);
+    match &node.value {
+        Value::Map(entries) => {
+            for (key, value) in entries {
+                out.push_str(&pad);
+                out.push_str(key);
+                out.push(':');
+                match inline(value) {
+                    Some(s) => {
+                        out.push(' ');
+                        out.push_str(&s);
+                        out.push('\n');
+                    }
+                    None => {
+                        out.push('\n');
+                        write_block(value, indent + 2, out);
+                    }
+                }
+            }
+        }
+        _ => match inline(node) {
+            Some(s) => {
+                out.push_str(&pad);
+                out.push_str(&s);
+                out.push('\n');
+            }
+            None => {
+                if let Value::List(items) = &node.value {
+                    for item in items {
+                        // the item's first line goes right after the dash;
+                        // an empty map writes no lines at all
+                        let mut inner = String::new();
+                        write_block(item, indent + 2, &mut inner);
+                        out.push_str(&pad);
+                        match inner.get(indent + 2..) {
+                            Some(rest) if !rest.is_empty() => {
+                                out.push_str("- ");
+                                out.push_str(rest);
+                            }
+                            _ => out.push_str("-\n"),
+                        }
+                    }
+                }
+            }
+        },
+    }
+}
+
+/// Writes `node` in block style, with lists of scalars in flow style.
+/// Scalars that would not read back as themselves are double-quoted, so
+/// they must not contain `"`. Empty maps cannot be written in this subset
+/// of YAML: one in a list becomes a bare `-`, which `parse` rejects.
+pub fn to_string(node: &Node) -> String {
+    let mut out = String::new();
+    write_block(node, 0, &mut out);
+    out
+}
+
+#[cfg(test)]
+mod yaml_tests {
+    use super::*;
+
+    fn scalar(line: usize, s: &str) -> Node {
+        Node {
+            line,
+            value: Value::Scalar(s.to_string()),
+        }
+    }
+
+    #[test]
+    fn nested() {
+        let doc = "\
+# a comment
+- add: sphere   # trailing comment
+  transform:
+    - [translate, 1, 0, 0]
+    - standard
+- define: name
+  value:
+    color: \"a # b\"
+";
+        let node = parse(doc).expect("valid");
+        let items = match node.value {
+            Value::List(items) => items,
+            _ => panic!("expected a list"),
+        };
+        assert_eq!(items.len(), 2);
+        assert_eq!(items[0].line, 2);
+        let first = match &items[0].value {
+            Value::Map(m) => m,
+            _ => panic!("expected a map"),
+        };
+        assert_eq!(first[0], (String::from("add"), scalar(2, "sphere")));
+        assert_eq!(first[1].0, "transform");
+        assert_eq!(
+            first[1].1.value,
+            Value::List(vec![
+                Node {
+                    line: 4,
+                    value: Value::List(vec![
+                        scalar(4, "translate"),
+                        scalar(4, "1"),
+                        scalar(4, "0"),
+                        scalar(4, "0")
+                    ])
+                },
+                scalar(5, "standard"),
+            ])
+        );
+        let value = match &items[1].value {
+            Value::Map(m) => &m[1].1,
+            _ => panic!("expected a map"),
+        };
+        assert_eq!(
+            value.value,
+            Value::Map(vec![(String::from("color"), scalar(8, "a # b"))])
+        );
+    }
+
+    #[test]
+    fn flow_lists() {
+        assert_eq!(
+            flow("[[1, 2], [], x]", 3).expect("valid").value,
+            Value::List(vec![
+                Node {
+                    line: 3,
+                    value: Value::List(vec![scalar(3, "1"), scalar(3, "2")])
+                },
+                Node {
+                    line: 3,
+                    value: Value::List(vec![])
+                },
+                scalar(3, "x"),
+            ])
+        );
+        // a list under a key may start at the key's own indentation
+        let node = parse("a:\n- 1\n- 2\nb: 3").expect("valid");
+        match node.value {
+            Value::Map(m) => assert_eq!(m.len(), 2),
+            _ => panic!("expected a map"),
+        }
+        assert_eq!(parse("").expect("valid").value, Value::List(vec![]));
+    }
+
+    #[test]
+    fn errors() {
+        let line = |doc: &str| parse(doc).expect_err("invalid").line;
+        assert_eq!(line("a: 1\n  b: 2"), 2);
+        assert_eq!(line("a: 1\na: 2"), 2);
+        assert_eq!(line("- a\n\n-"), 3);
+        assert_eq!(line("a:\n\tb: 1"), 2);
+        assert_eq!(line("a: [1, 2"), 1);
+        assert_eq!(line("a: [1,, 2]"), 1);
+        assert_eq!(line("x:\ny: 1"), 1);
+        assert_eq!(line("- a: 1\n  b: 2\n    c: 3"), 3);
+    }
+
+    #[test]
+    fn write() {
+        let doc = "\
+- add: sphere
+  transform:
+    - [translate, 1, 0, 0]
+    - [scale, 2, 2, 2]
+  empty: []
+  nested:
+    - - [1, 2]
+      - \"a, b\"
+- define: name
+  value:
+    color: \"#fff\"
+";
+        let node = parse(doc).expect("valid");
+        let text = to_string(&node);
+        assert_eq!(text, doc);
+        assert_eq!(parse(&text).expect("valid"), node);
+
+        let empty = Node {
+            line: 1,
+            value: Value::List(vec![
+                Node {
+                    line: 1,
+                    value: Value::Map(vec![]),
+                },
+                scalar(2, "a"),
+            ]),
+        };
+        assert_eq!(to_string(&empty), "-\n- a\n");
+        assert!(parse(&to_string(&empty)).is_err());
+    }
+}